@@ -0,0 +1,129 @@
+use anyhow::anyhow;
+
+use crate::wildcard;
+
+// Bot filtering
+//
+// What to do with events coming from bots and crawlers, configured with the piano_bot_filtering setting:
+// off (default) sends them as any other event, tag sends them with the is_bot property, drop doesn't send them.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) enum BotFiltering {
+    #[default]
+    Off,
+    Tag,
+    Drop,
+}
+
+impl BotFiltering {
+    pub(crate) fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "off" => Ok(Self::Off),
+            "tag" => Ok(Self::Tag),
+            "drop" => Ok(Self::Drop),
+            _ => Err(anyhow!("Invalid bot filtering: {}", value)),
+        }
+    }
+}
+
+// lowercased markers of known crawlers, HTTP libraries and headless browsers
+const BOT_MARKERS: &[&str] = &[
+    "bot",
+    "crawl",
+    "spider",
+    "slurp",
+    "mediapartners-google",
+    "facebookexternalhit",
+    "embedly",
+    "headlesschrome",
+    "phantomjs",
+    "puppeteer",
+    "playwright",
+    "selenium",
+    "webdriver",
+    "lighthouse",
+    "pagespeed",
+    "gtmetrix",
+    "pingdom",
+    "uptimerobot",
+    "python-requests",
+    "python-urllib",
+    "aiohttp",
+    "scrapy",
+    "curl/",
+    "wget/",
+    "go-http-client",
+    "java/",
+    "apache-httpclient",
+    "node-fetch",
+    "axios/",
+    "libwww-perl",
+];
+
+// legitimate user agents containing a bot marker (Cubot phones)
+const FALSE_POSITIVES: &[&str] = &["cubot"];
+
+// whether the user agent is a bot, from the known markers and the extra patterns of the piano_bot_patterns
+// setting (case-insensitive, matched anywhere in the user agent, * matches any characters)
+pub(crate) fn is_bot(user_agent: &str, extra_patterns: &[String]) -> bool {
+    let mut user_agent = user_agent.to_lowercase();
+    if extra_patterns
+        .iter()
+        .any(|pattern| wildcard::matches(&format!("*{}*", pattern.to_lowercase()), &user_agent))
+    {
+        return true;
+    }
+
+    for false_positive in FALSE_POSITIVES {
+        user_agent = user_agent.replace(false_positive, "");
+    }
+    BOT_MARKERS.iter().any(|marker| user_agent.contains(marker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_filtering() {
+        assert_eq!(BotFiltering::parse("").unwrap(), BotFiltering::Off);
+        assert_eq!(BotFiltering::parse(" Tag ").unwrap(), BotFiltering::Tag);
+        assert_eq!(BotFiltering::parse("drop").unwrap(), BotFiltering::Drop);
+        assert!(BotFiltering::parse("block").is_err());
+    }
+
+    #[test]
+    fn known_bots() {
+        for user_agent in [
+            "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)",
+            "Mozilla/5.0 (compatible; bingbot/2.0; +http://www.bing.com/bingbot.htm)",
+            "Mozilla/5.0 (compatible; Yahoo! Slurp; http://help.yahoo.com/help/us/ysearch/slurp)",
+            "facebookexternalhit/1.1 (+http://www.facebook.com/externalhit_uatext.php)",
+            "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) HeadlessChrome/124.0.0.0 Safari/537.36",
+            "Mozilla/5.0 (Linux; Android 11; moto g power (2022)) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Mobile Safari/537.36 Chrome-Lighthouse",
+            "python-requests/2.31.0",
+            "curl/8.4.0",
+        ] {
+            assert!(is_bot(user_agent, &[]), "{}", user_agent);
+        }
+    }
+
+    #[test]
+    fn browsers_are_not_bots() {
+        for user_agent in [
+            "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4.1 Safari/605.1.15",
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:125.0) Gecko/20100101 Firefox/125.0",
+            "Mozilla/5.0 (Linux; Android 12; CUBOT KINGKONG 7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Mobile Safari/537.36",
+            "",
+        ] {
+            assert!(!is_bot(user_agent, &[]), "{}", user_agent);
+        }
+    }
+
+    #[test]
+    fn extra_patterns() {
+        let patterns = vec!["MyMonitor".to_string(), "internal-*-checker".to_string()];
+        assert!(is_bot("Mozilla/5.0 mymonitor/1.0", &patterns));
+        assert!(is_bot("internal-uptime-checker/2", &patterns));
+        assert!(!is_bot("Mozilla/5.0 internal-uptime/2", &patterns));
+    }
+}
@@ -0,0 +1,117 @@
+#[cfg(feature = "campaigns")]
+use crate::piano_payload::parse_value;
+use crate::piano_payload::PianoData;
+#[cfg(feature = "campaigns")]
+use std::collections::HashMap;
+
+// Campaign parameters of the page querystring
+//
+// utm_* and at_* parameters override the standard campaign parameters coming from the Edgee context
+// https://developers.atinternet-solutions.com/piano-analytics/data-collection/how-to-send-events/marketing-campaigns
+//
+// the querystring parsing (and the serde_qs crate) is only compiled with the campaigns feature
+#[cfg(feature = "campaigns")]
+pub(crate) fn apply_search_parameters(
+    search: &str,
+    collect_utm_as_properties: bool,
+    data: &mut PianoData,
+) {
+    // analyze search string
+    if let Ok(qs_map) = serde_qs::from_str::<HashMap<String, String>>(search) {
+        for (key, value) in qs_map.iter() {
+            // key could start with ?
+            let key = key.trim_start_matches("?");
+
+            if key.starts_with("utm_") {
+                if collect_utm_as_properties {
+                    data.additional_fields
+                        .insert(key.to_string(), parse_value(value));
+                }
+                match key {
+                    "utm_campaign" => data.src_campaign = Some(value.clone()),
+                    "utm_content" => data.src_content = Some(value.clone()),
+                    "utm_medium" => data.src_medium = Some(value.clone()),
+                    "utm_creative_format" => data.src_creative_format = Some(value.clone()),
+                    "utm_id" => data.src_id = Some(value.clone()),
+                    "utm_marketing_tactic" => data.src_marketing_tactic = Some(value.clone()),
+                    "utm_source" => data.src_source = Some(value.clone()),
+                    "utm_source_platform" => data.src_source_platform = Some(value.clone()),
+                    "utm_term" => data.src_term = Some(value.clone()),
+                    _ => {
+                        if !collect_utm_as_properties {
+                            // replace utm_ with src_
+                            data.additional_fields
+                                .insert(key.replace("utm_", "src_"), parse_value(value));
+                            data.additional_fields
+                                .insert(key.to_string(), parse_value(value));
+                        }
+                    }
+                }
+            }
+            if key.starts_with("at_") {
+                match key {
+                    "at_campaign" => data.src_campaign = Some(value.clone()),
+                    "at_content" => data.src_content = Some(value.clone()),
+                    "at_medium" => data.src_medium = Some(value.clone()),
+                    "at_creative_format" => data.src_creative_format = Some(value.clone()),
+                    "at_id" => data.src_id = Some(value.clone()),
+                    "at_marketing_tactic" => data.src_marketing_tactic = Some(value.clone()),
+                    "at_source" => data.src_source = Some(value.clone()),
+                    "at_source_platform" => data.src_source_platform = Some(value.clone()),
+                    "at_term" => data.src_term = Some(value.clone()),
+                    _ => {
+                        // replace at_ with src_
+                        data.additional_fields
+                            .insert(key.replace("at_", "src_"), parse_value(value));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "campaigns"))]
+pub(crate) fn apply_search_parameters(
+    _search: &str,
+    _collect_utm_as_properties: bool,
+    _data: &mut PianoData,
+) {
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "campaigns")]
+    #[test]
+    fn utm_and_at_parameters() {
+        let mut data = PianoData::default();
+        apply_search_parameters(
+            "?utm_source=newsletter&at_medium=email&at_custom=1",
+            false,
+            &mut data,
+        );
+        assert_eq!(data.src_source, Some("newsletter".to_string()));
+        assert_eq!(data.src_medium, Some("email".to_string()));
+        assert!(data.additional_fields.contains_key("src_custom"));
+        assert!(!data.additional_fields.contains_key("utm_source"));
+    }
+
+    #[cfg(feature = "campaigns")]
+    #[test]
+    fn utm_as_properties() {
+        let mut data = PianoData::default();
+        apply_search_parameters("utm_campaign=spring", true, &mut data);
+        assert_eq!(data.src_campaign, Some("spring".to_string()));
+        assert!(data.additional_fields.contains_key("utm_campaign"));
+    }
+
+    #[cfg(not(feature = "campaigns"))]
+    #[test]
+    fn querystring_ignored_without_campaigns() {
+        let mut data = PianoData::default();
+        apply_search_parameters("utm_source=newsletter", true, &mut data);
+        assert_eq!(data.src_source, None);
+        assert!(data.additional_fields.is_empty());
+    }
+}
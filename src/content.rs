@@ -0,0 +1,210 @@
+use anyhow::anyhow;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+use crate::piano_payload::PianoData;
+
+// Piano standard content properties that can be populated from page properties
+//
+// https://developers.atinternet-solutions.com/piano-analytics/data-collection/how-to-send-events/standard-events#content
+const CONTENT_PROPERTIES: [&str; 5] = [
+    "content_id",
+    "content_type",
+    "content_author",
+    "content_publication_date",
+    "content_section",
+];
+
+// Maps page properties sent by the CMS to Piano content properties
+//
+// It can be configured with the piano_content_mapping setting,
+// e.g. "content_id=article_id,content_author=author"
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ContentMapping {
+    // (piano property, page property)
+    entries: Vec<(String, String)>,
+}
+
+impl Default for ContentMapping {
+    fn default() -> Self {
+        Self {
+            entries: vec![
+                ("content_id".to_string(), "article_id".to_string()),
+                ("content_type".to_string(), "type".to_string()),
+                ("content_author".to_string(), "author".to_string()),
+                (
+                    "content_publication_date".to_string(),
+                    "published_at".to_string(),
+                ),
+                ("content_section".to_string(), "section".to_string()),
+            ],
+        }
+    }
+}
+
+impl ContentMapping {
+    pub(crate) fn parse(value: &str) -> anyhow::Result<Self> {
+        let mut entries = vec![];
+        for entry in value.split(',') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let (piano_property, page_property) = match entry.split_once('=') {
+                Some((piano_property, page_property)) => {
+                    (piano_property.trim(), page_property.trim())
+                }
+                None => return Err(anyhow!("Invalid content mapping entry: {}", entry)),
+            };
+            if !CONTENT_PROPERTIES.contains(&piano_property) {
+                return Err(anyhow!("Unknown content property: {}", piano_property));
+            }
+            if page_property.is_empty() {
+                return Err(anyhow!("Invalid content mapping entry: {}", entry));
+            }
+            entries.push((piano_property.to_string(), page_property.to_string()));
+        }
+        Ok(Self { entries })
+    }
+
+    // set the content property mapped to the given page property,
+    // returns false if the page property is not mapped (or its value can't be used)
+    pub(crate) fn apply(&self, page_property: &str, value: &str, data: &mut PianoData) -> bool {
+        let piano_property = match self.entries.iter().find(|(_, from)| from == page_property) {
+            Some((piano_property, _)) => piano_property.as_str(),
+            None => return false,
+        };
+
+        match piano_property {
+            "content_id" => data.content_id = Some(value.to_string()),
+            "content_type" => data.content_type = Some(value.to_string()),
+            "content_author" => data.content_author = Some(value.to_string()),
+            "content_section" => data.content_section = Some(value.to_string()),
+            "content_publication_date" => match normalize_date(value) {
+                Some(date) => data.content_publication_date = Some(date),
+                None => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+// Piano expects dates as RFC 3339 strings in UTC (same format as cookie_creation_date)
+//
+// accepted inputs: "YYYYMMDD", unix timestamps (seconds or milliseconds), RFC 3339, "YYYY-MM-DD HH:MM:SS",
+// "YYYY-MM-DDTHH:MM:SS" and "YYYY-MM-DD" (naive dates are considered UTC)
+pub(crate) fn normalize_date(value: &str) -> Option<String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+
+    // 8 digits are a YYYYMMDD date rather than a 1970s timestamp
+    let compact_date = if value.len() == 8 {
+        NaiveDate::parse_from_str(value, "%Y%m%d").ok()
+    } else {
+        None
+    };
+
+    let datetime = if let Some(date) = compact_date {
+        date.and_hms_opt(0, 0, 0).map(|datetime| datetime.and_utc())
+    } else if let Ok(timestamp) = value.parse::<i64>() {
+        // anything above year 5138 in seconds is considered to be milliseconds
+        if timestamp.abs() >= 100_000_000_000 {
+            DateTime::from_timestamp_millis(timestamp)
+        } else {
+            DateTime::from_timestamp(timestamp, 0)
+        }
+    } else if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        Some(datetime.with_timezone(&Utc))
+    } else if let Ok(datetime) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        Some(datetime.and_utc())
+    } else if let Ok(datetime) = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S") {
+        Some(datetime.and_utc())
+    } else if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        date.and_hms_opt(0, 0, 0).map(|datetime| datetime.and_utc())
+    } else {
+        None
+    };
+
+    datetime.map(|datetime| datetime.to_rfc3339())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_mapping() {
+        let mapping = ContentMapping::default();
+        let mut data = PianoData::default();
+        assert!(mapping.apply("article_id", "42", &mut data));
+        assert!(mapping.apply("author", "Jane Doe", &mut data));
+        assert!(mapping.apply("section", "sports", &mut data));
+        assert!(mapping.apply("published_at", "2024-05-01", &mut data));
+        assert!(!mapping.apply("prop1", "value1", &mut data));
+        assert_eq!(data.content_id, Some("42".to_string()));
+        assert_eq!(data.content_author, Some("Jane Doe".to_string()));
+        assert_eq!(data.content_section, Some("sports".to_string()));
+        assert_eq!(
+            data.content_publication_date,
+            Some("2024-05-01T00:00:00+00:00".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_mapping() {
+        let mapping = ContentMapping::parse(" content_id = id , content_type=kind,").unwrap();
+        let mut data = PianoData::default();
+        assert!(mapping.apply("id", "abc", &mut data));
+        assert!(mapping.apply("kind", "article", &mut data));
+        assert!(!mapping.apply("article_id", "42", &mut data));
+        assert_eq!(data.content_id, Some("abc".to_string()));
+        assert_eq!(data.content_type, Some("article".to_string()));
+    }
+
+    #[test]
+    fn parse_mapping_invalid() {
+        assert!(ContentMapping::parse("content_id").is_err());
+        assert!(ContentMapping::parse("content_id=").is_err());
+        assert!(ContentMapping::parse("content_whatever=id").is_err());
+    }
+
+    #[test]
+    fn invalid_publication_date_is_not_mapped() {
+        let mapping = ContentMapping::default();
+        let mut data = PianoData::default();
+        assert!(!mapping.apply("published_at", "yesterday", &mut data));
+        assert_eq!(data.content_publication_date, None);
+    }
+
+    #[test]
+    fn normalize_date_formats() {
+        let expected = Some("2024-03-31T01:30:00+00:00".to_string());
+        assert_eq!(normalize_date("1711848600"), expected);
+        assert_eq!(normalize_date("1711848600000"), expected);
+        assert_eq!(normalize_date("2024-03-31T01:30:00Z"), expected);
+        assert_eq!(normalize_date("2024-03-31T03:30:00+02:00"), expected);
+        assert_eq!(normalize_date("2024-03-31 01:30:00"), expected);
+        assert_eq!(normalize_date("2024-03-31T01:30:00"), expected);
+        assert_eq!(
+            normalize_date(" 2024-03-31 "),
+            Some("2024-03-31T00:00:00+00:00".to_string())
+        );
+        assert_eq!(normalize_date(""), None);
+        assert_eq!(normalize_date("31/03/2024"), None);
+    }
+
+    #[test]
+    fn normalize_compact_date() {
+        assert_eq!(
+            normalize_date("20240501"),
+            Some("2024-05-01T00:00:00+00:00".to_string())
+        );
+        // not a valid date, considered as a timestamp
+        assert_eq!(
+            normalize_date("99999999"),
+            Some("1973-03-03T09:46:39+00:00".to_string())
+        );
+    }
+}
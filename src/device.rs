@@ -0,0 +1,62 @@
+use crate::piano_payload::PianoData;
+
+// Viewport
+//
+// device_display_* is the browser viewport (as sent by the Piano SDK), which Edgee doesn't collect:
+// it can be provided with the viewport_width and viewport_height page or track properties.
+// returns false if the property is not a viewport property (or its value is not a positive integer)
+pub(crate) fn apply_viewport_property(key: &str, value: &str, data: &mut PianoData) -> bool {
+    let size = match value.trim().parse::<i64>() {
+        Ok(size) if size > 0 => size,
+        _ => return false,
+    };
+    match key {
+        "viewport_width" => data.device_display_width = Some(size),
+        "viewport_height" => data.device_display_height = Some(size),
+        _ => return false,
+    }
+    true
+}
+
+// device pixel ratio, rounded to 2 decimals
+pub(crate) fn pixel_ratio(screen_density: f32) -> Option<f64> {
+    if screen_density.is_finite() && screen_density > 0.0 {
+        Some((screen_density as f64 * 100.0).round() / 100.0)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn viewport_properties() {
+        let mut data = PianoData::default();
+        assert!(apply_viewport_property("viewport_width", "1280", &mut data));
+        assert!(apply_viewport_property(
+            "viewport_height",
+            " 720 ",
+            &mut data
+        ));
+        assert!(!apply_viewport_property(
+            "viewport_width",
+            "wide",
+            &mut data
+        ));
+        assert!(!apply_viewport_property("viewport_height", "-1", &mut data));
+        assert!(!apply_viewport_property("prop2", "10", &mut data));
+        assert_eq!(data.device_display_width, Some(1280));
+        assert_eq!(data.device_display_height, Some(720));
+    }
+
+    #[test]
+    fn pixel_ratios() {
+        assert_eq!(pixel_ratio(2.0), Some(2.0));
+        assert_eq!(pixel_ratio(1.1), Some(1.1));
+        assert_eq!(pixel_ratio(2.625), Some(2.63));
+        assert_eq!(pixel_ratio(0.0), None);
+        assert_eq!(pixel_ratio(f32::NAN), None);
+    }
+}
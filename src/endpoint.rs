@@ -0,0 +1,146 @@
+use anyhow::anyhow;
+
+// Collection protocol, configured with the piano_protocol setting
+//
+// event (default) posts the events to the Piano collection API endpoint (/event),
+// as2 sends them as Analytics Suite 2 GET hits to the legacy hit.xiti endpoint of old sites (see legacy_hit)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) enum Protocol {
+    #[default]
+    Event,
+    As2,
+}
+
+impl Protocol {
+    pub(crate) fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "event" => Ok(Self::Event),
+            "as2" => Ok(Self::As2),
+            _ => Err(anyhow!("Invalid protocol: {}", value)),
+        }
+    }
+
+    // path of the endpoint, unless overridden with the piano_collection_path setting
+    pub(crate) fn default_path(self) -> &'static str {
+        match self {
+            Self::Event => "/event",
+            Self::As2 => "/hit.xiti",
+        }
+    }
+}
+
+// Scheme of the collection endpoint, configured with the piano_collection_scheme setting
+//
+// http is only meant for local stand-ins of the collection endpoint
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) enum Scheme {
+    #[default]
+    Https,
+    Http,
+}
+
+impl Scheme {
+    pub(crate) fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "https" => Ok(Self::Https),
+            "http" => Ok(Self::Http),
+            _ => Err(anyhow!("Invalid scheme: {}", value)),
+        }
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Https => "https",
+            Self::Http => "http",
+        }
+    }
+}
+
+// Content type of the request body, configured with the piano_content_type setting
+//
+// the body is the same JSON payload either way, text/plain (default) is what the Piano SDK sends
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) enum ContentType {
+    #[default]
+    TextPlain,
+    Json,
+}
+
+impl ContentType {
+    pub(crate) fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "text/plain" => Ok(Self::TextPlain),
+            "application/json" => Ok(Self::Json),
+            _ => Err(anyhow!("Invalid content type: {}", value)),
+        }
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::TextPlain => "text/plain",
+            Self::Json => "application/json",
+        }
+    }
+}
+
+// custom path of the collection endpoint (e.g. for a first-party proxy), always starting with /
+pub(crate) fn normalize_path(value: &str) -> anyhow::Result<String> {
+    let value = value.trim();
+    if value
+        .chars()
+        .any(|c| c.is_whitespace() || c.is_control() || c == '?' || c == '#')
+    {
+        return Err(anyhow!("Invalid collection path: {}", value));
+    }
+    if value.starts_with('/') {
+        Ok(value.to_string())
+    } else {
+        Ok(format!("/{}", value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_protocol() {
+        assert_eq!(Protocol::parse("event").unwrap(), Protocol::Event);
+        assert_eq!(Protocol::parse(" AS2 ").unwrap(), Protocol::As2);
+        assert!(Protocol::parse("hit_xiti").is_err());
+        assert_eq!(Protocol::Event.default_path(), "/event");
+        assert_eq!(Protocol::As2.default_path(), "/hit.xiti");
+    }
+
+    #[test]
+    fn parse_scheme() {
+        assert_eq!(Scheme::parse("https").unwrap(), Scheme::Https);
+        assert_eq!(Scheme::parse("HTTP").unwrap().as_str(), "http");
+        assert!(Scheme::parse("ftp").is_err());
+    }
+
+    #[test]
+    fn parse_content_type() {
+        assert_eq!(
+            ContentType::parse("text/plain").unwrap(),
+            ContentType::TextPlain
+        );
+        assert_eq!(
+            ContentType::parse(" Application/JSON ").unwrap().as_str(),
+            "application/json"
+        );
+        assert!(ContentType::parse("application/gzip").is_err());
+    }
+
+    #[test]
+    fn collection_path() {
+        assert_eq!(normalize_path("/collect").unwrap(), "/collect");
+        assert_eq!(
+            normalize_path(" analytics/event ").unwrap(),
+            "/analytics/event"
+        );
+        assert!(normalize_path("/event?s=1").is_err());
+        assert!(normalize_path("/my path").is_err());
+        assert!(normalize_path("/event#top").is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use std::fmt;
+
+// Errors returned to Edgee
+//
+// Messages start with a stable error code (e.g. "[PIANO_CONFIG] Missing piano site id"),
+// so Edgee logs can be aggregated and alerted on by error category.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum PianoError {
+    // invalid or missing settings
+    Config(String),
+    // event without the data expected for its type
+    MissingData(String),
+    // event not sent because of the bot filtering
+    Filtered(String),
+    // event data Piano can't accept
+    Validation(String),
+    // event type not sent to Piano (user events)
+    Unsupported(String),
+    Serialization(String),
+}
+
+impl PianoError {
+    // settings errors keep the context naming the setting
+    pub(crate) fn config(error: anyhow::Error) -> Self {
+        Self::Config(format!("{:#}", error))
+    }
+
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            Self::Config(_) => "PIANO_CONFIG",
+            Self::MissingData(_) => "PIANO_MISSING_DATA",
+            Self::Filtered(_) => "PIANO_FILTERED",
+            Self::Validation(_) => "PIANO_VALIDATION",
+            Self::Unsupported(_) => "PIANO_UNSUPPORTED_EVENT",
+            Self::Serialization(_) => "PIANO_SERIALIZATION",
+        }
+    }
+
+    pub(crate) fn message(&self) -> &str {
+        match self {
+            Self::Config(message)
+            | Self::MissingData(message)
+            | Self::Filtered(message)
+            | Self::Validation(message)
+            | Self::Unsupported(message)
+            | Self::Serialization(message) => message,
+        }
+    }
+}
+
+impl fmt::Display for PianoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.code(), self.message())
+    }
+}
+
+impl std::error::Error for PianoError {}
+
+// guest functions return the error as a string
+impl From<PianoError> for String {
+    fn from(error: PianoError) -> Self {
+        error.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Context};
+
+    #[test]
+    fn message_starts_with_code() {
+        let error = PianoError::MissingData("Missing page data".to_string());
+        assert_eq!(error.code(), "PIANO_MISSING_DATA");
+        assert_eq!(
+            String::from(error),
+            "[PIANO_MISSING_DATA] Missing page data"
+        );
+    }
+
+    #[test]
+    fn config_error_keeps_context() {
+        let error: anyhow::Result<()> = Err(anyhow!("Site id must be numeric, got abc"));
+        let error = PianoError::config(error.context("Invalid piano_site_id setting").unwrap_err());
+        assert_eq!(
+            error.to_string(),
+            "[PIANO_CONFIG] Invalid piano_site_id setting: Site id must be numeric, got abc"
+        );
+    }
+}
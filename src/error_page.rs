@@ -0,0 +1,173 @@
+use crate::exports::edgee::components::data_collection::PageData;
+use crate::wildcard;
+
+// Error pages detection
+//
+// error pages are flagged with is_error_page, and their page property is prefixed with error_<status code>::
+
+// error page titles (lowercased), and the status code they reveal
+//
+// a title or name is matched as a whole, or by its first or last part separated by a delimiter
+// (e.g. "Page not found | Example"), so that content pages like "Lost and not found" are not matched
+const TITLES: [(&str, u16); 12] = [
+    ("404", 404),
+    ("page not found", 404),
+    ("not found", 404),
+    ("error 404", 404),
+    ("404 error", 404),
+    ("404 not found", 404),
+    ("500", 500),
+    ("internal server error", 500),
+    ("server error", 500),
+    ("error 500", 500),
+    ("500 error", 500),
+    ("500 internal server error", 500),
+];
+
+// delimiters between the parts of a title, e.g. the page and site names
+// (not ":", used within content titles like "Episode 3: Not Found")
+const TITLE_DELIMITERS: [&str; 5] = ["|", " - ", " – ", " — ", "·"];
+
+// returns the status code of the page if it is detected as an error page, using (in order):
+// - the status_code page property
+// - the page path, matched against the url patterns (considered as 404)
+// - the page title and name
+pub(crate) fn detect(data: &PageData, url_patterns: &[String]) -> Option<u16> {
+    if let Some((_, status_code)) = data.properties.iter().find(|(key, _)| key == "status_code") {
+        if let Ok(status_code) = status_code.trim().parse::<u16>() {
+            return (400..600).contains(&status_code).then_some(status_code);
+        }
+    }
+
+    if url_patterns
+        .iter()
+        .any(|pattern| wildcard::matches(pattern, &data.path))
+    {
+        return Some(404);
+    }
+
+    for text in [&data.title, &data.name] {
+        if let Some(status_code) = title_status_code(text) {
+            return Some(status_code);
+        }
+    }
+
+    None
+}
+
+fn title_status_code(text: &str) -> Option<u16> {
+    let mut parts = vec![text.to_lowercase()];
+    for delimiter in TITLE_DELIMITERS {
+        parts = parts
+            .iter()
+            .flat_map(|part| part.split(delimiter))
+            .map(str::to_string)
+            .collect();
+    }
+    let status_code = |part: &String| {
+        TITLES
+            .iter()
+            .find(|(title, _)| part.trim() == *title)
+            .map(|(_, status_code)| *status_code)
+    };
+    parts
+        .first()
+        .and_then(status_code)
+        .or_else(|| parts.last().and_then(status_code))
+}
+
+// page property of an error page
+pub(crate) fn page_name(status_code: u16, page: &str) -> String {
+    format!("error_{}::{}", status_code, page)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page_data(title: &str, path: &str, properties: Vec<(&str, &str)>) -> PageData {
+        PageData {
+            name: "".to_string(),
+            category: "".to_string(),
+            keywords: vec![],
+            title: title.to_string(),
+            url: format!("https://example.com{}", path),
+            path: path.to_string(),
+            search: "".to_string(),
+            referrer: "".to_string(),
+            properties: properties
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn detect_from_status_code() {
+        let data = page_data("Home", "/", vec![("status_code", "410")]);
+        assert_eq!(detect(&data, &[]), Some(410));
+
+        let data = page_data("Home", "/", vec![("status_code", "200")]);
+        assert_eq!(detect(&data, &[]), None);
+
+        // status_code takes precedence over the title
+        let data = page_data("Page not found", "/", vec![("status_code", "200")]);
+        assert_eq!(detect(&data, &[]), None);
+    }
+
+    #[test]
+    fn detect_from_title() {
+        let data = page_data("Page Not Found | Example", "/whatever", vec![]);
+        assert_eq!(detect(&data, &[]), Some(404));
+
+        let data = page_data("404", "/whatever", vec![]);
+        assert_eq!(detect(&data, &[]), Some(404));
+
+        let data = page_data("Internal Server Error", "/whatever", vec![]);
+        assert_eq!(detect(&data, &[]), Some(500));
+
+        let data = page_data("404 - Not Found", "/whatever", vec![]);
+        assert_eq!(detect(&data, &[]), Some(404));
+
+        let data = page_data("Example · Error 500", "/whatever", vec![]);
+        assert_eq!(detect(&data, &[]), Some(500));
+
+        let data = page_data("Top 500 companies", "/whatever", vec![]);
+        assert_eq!(detect(&data, &[]), None);
+    }
+
+    #[test]
+    fn content_titles_are_not_error_pages() {
+        for title in [
+            "Lost and not found",
+            "Fixing the server error in X",
+            "Why 404 - pages matter | Blog",
+            "Error 404 explained",
+            "Server errors: a guide",
+            "Fortune 500 - the list",
+            "Episode 3: Not Found",
+            "Series | Not found | Episode 3",
+        ] {
+            let data = page_data(title, "/articles/1", vec![]);
+            assert_eq!(detect(&data, &[]), None, "{}", title);
+        }
+    }
+
+    #[test]
+    fn detect_from_url_patterns() {
+        let patterns = vec!["/errors/*".to_string(), "*/not-found".to_string()];
+        let data = page_data("Oops", "/errors/missing", vec![]);
+        assert_eq!(detect(&data, &patterns), Some(404));
+
+        let data = page_data("Oops", "/fr/not-found", vec![]);
+        assert_eq!(detect(&data, &patterns), Some(404));
+
+        let data = page_data("Oops", "/articles/1", vec![]);
+        assert_eq!(detect(&data, &patterns), None);
+    }
+
+    #[test]
+    fn error_page_name() {
+        assert_eq!(page_name(404, "page title"), "error_404::page title");
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::anyhow;
+
+use crate::exports::edgee::components::data_collection::Client;
+use crate::piano_payload::PianoData;
+
+// Geo granularity
+//
+// Most precise geo level sent to Piano, configured with the piano_geo_granularity setting.
+// Without consent, geo data is never more precise than the country.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum GeoGranularity {
+    None,
+    Continent,
+    Country,
+    Region,
+    #[default]
+    City,
+}
+
+impl GeoGranularity {
+    pub(crate) fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "continent" => Ok(Self::Continent),
+            "country" => Ok(Self::Country),
+            "region" => Ok(Self::Region),
+            "city" => Ok(Self::City),
+            _ => Err(anyhow!("Invalid geo granularity: {}", value)),
+        }
+    }
+
+    // set the geo properties allowed by the granularity
+    pub(crate) fn apply(self, client: &Client, consent_granted: bool, data: &mut PianoData) {
+        let granularity = if consent_granted {
+            self
+        } else {
+            self.min(Self::Country)
+        };
+
+        if granularity >= Self::Continent && !client.continent.is_empty() {
+            data.geo_continent = Some(client.continent.clone());
+        }
+        if granularity >= Self::Country {
+            if !client.country_code.is_empty() {
+                data.geo_country_code_alpha2 = Some(client.country_code.clone());
+            }
+            if !client.country_name.is_empty() {
+                data.geo_country = Some(client.country_name.clone());
+            }
+        }
+        if granularity >= Self::Region && !client.region.is_empty() {
+            data.geo_region = Some(client.region.clone());
+        }
+        if granularity >= Self::City && !client.city.is_empty() {
+            data.geo_city = Some(client.city.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client() -> Client {
+        Client {
+            city: "Lyon".to_string(),
+            ip: "".to_string(),
+            locale: "".to_string(),
+            timezone: "".to_string(),
+            user_agent: "".to_string(),
+            user_agent_architecture: "".to_string(),
+            user_agent_bitness: "".to_string(),
+            user_agent_full_version_list: "".to_string(),
+            user_agent_version_list: "".to_string(),
+            user_agent_mobile: "".to_string(),
+            user_agent_model: "".to_string(),
+            os_name: "".to_string(),
+            os_version: "".to_string(),
+            screen_width: 0,
+            screen_height: 0,
+            screen_density: 0.0,
+            continent: "Europe".to_string(),
+            country_code: "FR".to_string(),
+            country_name: "France".to_string(),
+            region: "Auvergne-Rhone-Alpes".to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_granularity() {
+        assert_eq!(GeoGranularity::parse("none").unwrap(), GeoGranularity::None);
+        assert_eq!(
+            GeoGranularity::parse(" Region ").unwrap(),
+            GeoGranularity::Region
+        );
+        assert!(GeoGranularity::parse("street").is_err());
+    }
+
+    #[test]
+    fn city_with_consent() {
+        let mut data = PianoData::default();
+        GeoGranularity::City.apply(&client(), true, &mut data);
+        assert_eq!(data.geo_continent, Some("Europe".to_string()));
+        assert_eq!(data.geo_country_code_alpha2, Some("FR".to_string()));
+        assert_eq!(data.geo_country, Some("France".to_string()));
+        assert_eq!(data.geo_region, Some("Auvergne-Rhone-Alpes".to_string()));
+        assert_eq!(data.geo_city, Some("Lyon".to_string()));
+    }
+
+    #[test]
+    fn city_without_consent_is_capped_to_country() {
+        let mut data = PianoData::default();
+        GeoGranularity::City.apply(&client(), false, &mut data);
+        assert_eq!(data.geo_continent, Some("Europe".to_string()));
+        assert_eq!(data.geo_country_code_alpha2, Some("FR".to_string()));
+        assert_eq!(data.geo_country, Some("France".to_string()));
+        assert_eq!(data.geo_region, None);
+        assert_eq!(data.geo_city, None);
+    }
+
+    #[test]
+    fn region_with_consent() {
+        let mut data = PianoData::default();
+        GeoGranularity::Region.apply(&client(), true, &mut data);
+        assert_eq!(data.geo_region, Some("Auvergne-Rhone-Alpes".to_string()));
+        assert_eq!(data.geo_city, None);
+    }
+
+    #[test]
+    fn continent_and_none() {
+        let mut data = PianoData::default();
+        GeoGranularity::Continent.apply(&client(), true, &mut data);
+        assert_eq!(data.geo_continent, Some("Europe".to_string()));
+        assert_eq!(data.geo_country_code_alpha2, None);
+
+        let mut data = PianoData::default();
+        GeoGranularity::None.apply(&client(), true, &mut data);
+        assert_eq!(data.geo_continent, None);
+        assert_eq!(data.geo_country_code_alpha2, None);
+    }
+}
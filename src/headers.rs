@@ -0,0 +1,183 @@
+use anyhow::anyhow;
+
+use crate::exports::edgee::components::data_collection::{Client, Consent, Event};
+use crate::ip;
+
+// Client headers sent explicitly to Piano, configured with the piano_forwarded_headers setting
+// (comma-separated header names). Client headers are then no longer forwarded by Edgee.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ForwardedHeader {
+    // client IP, truncated without consent
+    XForwardedFor,
+    UserAgent,
+    // client locale
+    AcceptLanguage,
+}
+
+impl ForwardedHeader {
+    pub(crate) fn parse_list(value: &str) -> anyhow::Result<Vec<Self>> {
+        let mut headers = vec![];
+        for name in value.split(',') {
+            let header = match name.trim().to_lowercase().as_str() {
+                "" => continue,
+                "x-forwarded-for" => Self::XForwardedFor,
+                "user-agent" => Self::UserAgent,
+                "accept-language" => Self::AcceptLanguage,
+                _ => return Err(anyhow!("Unsupported forwarded header: {}", name.trim())),
+            };
+            if !headers.contains(&header) {
+                headers.push(header);
+            }
+        }
+        Ok(headers)
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::XForwardedFor => "x-forwarded-for",
+            Self::UserAgent => "user-agent",
+            Self::AcceptLanguage => "accept-language",
+        }
+    }
+
+    // value of the header for the event, None if the client data is missing
+    pub(crate) fn value(self, edgee_event: &Event) -> Option<String> {
+        let client: &Client = &edgee_event.context.client;
+        let value = match self {
+            Self::XForwardedFor => {
+                ip::client_ip(&client.ip, edgee_event.consent == Some(Consent::Granted))?
+            }
+            Self::UserAgent => client.user_agent.clone(),
+            Self::AcceptLanguage => client.locale.clone(),
+        };
+        if value.is_empty() {
+            None
+        } else {
+            Some(value)
+        }
+    }
+}
+
+// Static headers added to every request (e.g. an authorization header for a first-party proxy),
+// configured with the piano_custom_headers setting: comma-separated <name>=<value> entries,
+// commas and backslashes within values being escaped as \, and \\
+pub(crate) fn parse_custom_headers(value: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let mut headers = vec![];
+    for entry in split_entries(value) {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+        let (name, value) = match entry.split_once('=') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => return Err(anyhow!("Invalid custom header: {}", entry)),
+        };
+        if !is_token(name) {
+            return Err(anyhow!("Invalid custom header name: {}", name));
+        }
+        if value.chars().any(|c| c.is_control()) {
+            return Err(anyhow!("Invalid custom header value for {}", name));
+        }
+        headers.push((name.to_lowercase(), value.to_string()));
+    }
+    Ok(headers)
+}
+
+// split on unescaped commas, unescaping \, and \\ (other backslashes are kept)
+fn split_entries(value: &str) -> Vec<String> {
+    let mut entries = vec![];
+    let mut entry = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some(&next)) if next == ',' || next == '\\' => {
+                entry.push(next);
+                chars.next();
+            }
+            (',', _) => entries.push(std::mem::take(&mut entry)),
+            (c, _) => entry.push(c),
+        }
+    }
+    entries.push(entry);
+    entries
+}
+
+// header names are RFC 9110 tokens
+fn is_token(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+}
+
+// set a header, replacing a previous one with the same name
+pub(crate) fn set(headers: &mut Vec<(String, String)>, name: &str, value: &str) {
+    match headers
+        .iter_mut()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+    {
+        Some(header) => header.1 = value.to_string(),
+        None => headers.push((name.to_string(), value.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_forwarded_headers() {
+        assert_eq!(
+            ForwardedHeader::parse_list(" User-Agent, accept-language,,user-agent").unwrap(),
+            vec![ForwardedHeader::UserAgent, ForwardedHeader::AcceptLanguage]
+        );
+        assert!(ForwardedHeader::parse_list("cookie").is_err());
+        assert!(ForwardedHeader::parse_list("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_custom_header_entries() {
+        assert_eq!(
+            parse_custom_headers("Authorization=Basic dXNlcjpwYXNz==, X-Proxy=edge,").unwrap(),
+            vec![
+                (
+                    "authorization".to_string(),
+                    "Basic dXNlcjpwYXNz==".to_string()
+                ),
+                ("x-proxy".to_string(), "edge".to_string()),
+            ]
+        );
+        assert!(parse_custom_headers("Authorization").is_err());
+        assert!(parse_custom_headers("Bad Header=1").is_err());
+        assert!(parse_custom_headers("=1").is_err());
+        assert!(parse_custom_headers("X-Test=a\nb").is_err());
+    }
+
+    #[test]
+    fn custom_header_values_with_commas() {
+        assert_eq!(
+            parse_custom_headers(r"Accept=text/html\, application/json, X-Path=C:\\dir\x").unwrap(),
+            vec![
+                (
+                    "accept".to_string(),
+                    "text/html, application/json".to_string()
+                ),
+                ("x-path".to_string(), r"C:\dir\x".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn set_header() {
+        let mut headers = vec![("content-type".to_string(), "text/plain".to_string())];
+        set(&mut headers, "Content-Type", "application/json");
+        set(&mut headers, "x-proxy", "edge");
+        assert_eq!(
+            headers,
+            vec![
+                ("content-type".to_string(), "application/json".to_string()),
+                ("x-proxy".to_string(), "edge".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+// Client IP anonymization
+//
+// IPv4 addresses are truncated to their /24 network (last octet set to 0),
+// IPv6 addresses to their /48 network
+pub(crate) fn truncate(ip: &str) -> Option<String> {
+    let ip: IpAddr = ip.trim().parse().ok()?;
+    let truncated = match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            IpAddr::V4(Ipv4Addr::new(a, b, c, 0))
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            IpAddr::V6(Ipv6Addr::new(
+                segments[0],
+                segments[1],
+                segments[2],
+                0,
+                0,
+                0,
+                0,
+                0,
+            ))
+        }
+    };
+    Some(truncated.to_string())
+}
+
+// client IP sent to Piano: the full IP if consent is granted, the truncated IP otherwise
+pub(crate) fn client_ip(ip: &str, consent_granted: bool) -> Option<String> {
+    if consent_granted {
+        let ip: IpAddr = ip.trim().parse().ok()?;
+        Some(ip.to_string())
+    } else {
+        truncate(ip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_ipv4() {
+        assert_eq!(truncate("192.168.12.34"), Some("192.168.12.0".to_string()));
+        assert_eq!(truncate(" 10.0.0.1 "), Some("10.0.0.0".to_string()));
+    }
+
+    #[test]
+    fn truncate_ipv6() {
+        assert_eq!(
+            truncate("2001:db8:85a3:8d3:1319:8a2e:370:7348"),
+            Some("2001:db8:85a3::".to_string())
+        );
+        assert_eq!(truncate("::1"), Some("::".to_string()));
+    }
+
+    #[test]
+    fn truncate_invalid() {
+        assert_eq!(truncate(""), None);
+        assert_eq!(truncate("not an ip"), None);
+        assert_eq!(truncate("192.168.0"), None);
+    }
+
+    #[test]
+    fn client_ip_with_and_without_consent() {
+        assert_eq!(
+            client_ip("192.168.12.34", true),
+            Some("192.168.12.34".to_string())
+        );
+        assert_eq!(
+            client_ip("192.168.12.34", false),
+            Some("192.168.12.0".to_string())
+        );
+        assert_eq!(client_ip("whatever", true), None);
+    }
+}
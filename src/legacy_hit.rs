@@ -0,0 +1,258 @@
+use anyhow::anyhow;
+use chrono::{DateTime, Duration, Timelike};
+
+use crate::error::PianoError;
+use crate::piano_payload::{PianoData, PianoEvent};
+
+// Legacy AT Internet Analytics Suite 2 hits
+//
+// With the as2 protocol, events are sent as classic hit.xiti GET requests (?s=...&p=...&x1=...)
+// so that sites still tagged with Analytics Suite 2 keep feeding their legacy reports.
+// Page events are page hits (p is the page name, or the page title without name), other events are
+// action clicks (type=click&click=A). The visitor is only identified (idclient and at) when opted in.
+
+// Site custom variables (x1, x2...) mapped from event properties, configured with the
+// piano_as2_site_variables setting: comma-separated <index>=<property> entries, e.g. "1=content_type,2=author"
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct SiteVariables {
+    // (index, property)
+    entries: Vec<(u32, String)>,
+}
+
+impl SiteVariables {
+    pub(crate) fn parse(value: &str) -> anyhow::Result<Self> {
+        let mut entries = vec![];
+        for entry in value.split(',') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let (index, property) = match entry.split_once('=') {
+                Some((index, property)) if !property.trim().is_empty() => {
+                    (index.trim(), property.trim())
+                }
+                _ => return Err(anyhow!("Invalid site variable: {}", entry)),
+            };
+            let index = index.trim_start_matches(['x', 'X']);
+            match index.parse::<u32>() {
+                Ok(index) if index > 0 => entries.push((index, property.to_string())),
+                _ => return Err(anyhow!("Invalid site variable index: {}", entry)),
+            }
+        }
+        Ok(Self { entries })
+    }
+}
+
+// querystring parameters of the hit, in order (ref must be the last one)
+pub(crate) fn parameters(
+    site_id: &str,
+    id_client: Option<&str>,
+    event: &PianoEvent,
+    site_variables: &SiteVariables,
+) -> Result<Vec<(String, String)>, PianoError> {
+    let data = &event.data;
+    // exempt and optout visitors are not identified
+    let opted_in = data.visitor_privacy_mode == "optin";
+    let mut parameters = vec![("s".to_string(), site_id.to_string())];
+    if let (true, Some(id_client)) = (opted_in, id_client) {
+        parameters.push(("idclient".to_string(), id_client.to_string()));
+    }
+    parameters.push(("ts".to_string(), data.device_timestamp_utc.to_string()));
+
+    if event.name == "page.display" {
+        let page = data
+            .page_name
+            .as_ref()
+            .or(data.page.as_ref())
+            .cloned()
+            .unwrap_or_default();
+        parameters.push(("p".to_string(), page));
+    } else {
+        parameters.push(("type".to_string(), "click".to_string()));
+        parameters.push(("click".to_string(), "A".to_string()));
+        parameters.push(("p".to_string(), event.name.clone()));
+    }
+
+    // site custom variables are looked up in the serialized event, so additional properties can be mapped
+    let properties = serde_json::to_value(data)
+        .map_err(|e| PianoError::Serialization(format!("Failed to serialize the event: {}", e)))?;
+    for (index, property) in &site_variables.entries {
+        let value = match properties.get(property) {
+            Some(serde_json::Value::String(value)) => value.clone(),
+            Some(serde_json::Value::Array(values)) => values
+                .iter()
+                .map(|value| match value {
+                    serde_json::Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+            Some(serde_json::Value::Null) | None => continue,
+            Some(value) => value.to_string(),
+        };
+        parameters.push((format!("x{}", index), value));
+    }
+
+    if data.device_screen_width > 0 && data.device_screen_height > 0 {
+        parameters.push((
+            "r".to_string(),
+            format!("{}x{}", data.device_screen_width, data.device_screen_height),
+        ));
+    }
+    if let (Some(width), Some(height)) = (data.device_display_width, data.device_display_height) {
+        parameters.push(("re".to_string(), format!("{}x{}", width, height)));
+    }
+    if let Some(local_time) = local_time(data) {
+        parameters.push(("hl".to_string(), local_time));
+    }
+    if !data.browser_language.is_empty() {
+        // browser_language_local is the uppercased language when the locale has no region
+        let language = if data.browser_language_local.is_empty()
+            || data.browser_language_local == data.browser_language.to_uppercase()
+        {
+            data.browser_language.clone()
+        } else {
+            format!("{}-{}", data.browser_language, data.browser_language_local)
+        };
+        parameters.push(("lng".to_string(), language));
+    }
+    if let (true, Some(user_id)) = (opted_in, &data.user_id) {
+        parameters.push(("at".to_string(), user_id.clone()));
+    }
+    if let Some(ref referrer) = data.previous_url {
+        if !referrer.is_empty() {
+            parameters.push(("ref".to_string(), referrer.clone()));
+        }
+    }
+    Ok(parameters)
+}
+
+// local time of the device, as HHxMMxSS
+fn local_time(data: &PianoData) -> Option<String> {
+    let utc = DateTime::from_timestamp_millis(data.device_timestamp_utc)?;
+    let local = utc + Duration::minutes(data.device_utc_offset.unwrap_or(0) as i64);
+    Some(format!(
+        "{}x{}x{}",
+        local.hour(),
+        local.minute(),
+        local.second()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page_event() -> PianoEvent {
+        PianoEvent {
+            name: "page.display".to_string(),
+            data: PianoData {
+                page: Some("Home page".to_string()),
+                page_name: Some("home".to_string()),
+                content_type: Some("article".to_string()),
+                content_keywords: Some(vec!["a".to_string(), "b".to_string()]),
+                device_screen_width: 1920,
+                device_screen_height: 1080,
+                device_timestamp_utc: 1714571100000, // 2024-05-01T13:45:00Z
+                device_utc_offset: Some(120),
+                browser_language: "en".to_string(),
+                browser_language_local: "GB".to_string(),
+                previous_url: Some("https://www.google.com/?q=a&b".to_string()),
+                visitor_privacy_mode: "optin".to_string(),
+                ..PianoData::default()
+            },
+        }
+    }
+
+    #[test]
+    fn parse_site_variables() {
+        let variables = SiteVariables::parse("1=content_type, x2 = author,").unwrap();
+        assert_eq!(
+            variables.entries,
+            vec![(1, "content_type".to_string()), (2, "author".to_string())]
+        );
+        assert!(SiteVariables::parse("0=content_type").is_err());
+        assert!(SiteVariables::parse("a=content_type").is_err());
+        assert!(SiteVariables::parse("1=").is_err());
+        assert!(SiteVariables::parse("content_type").is_err());
+    }
+
+    #[test]
+    fn page_hit() {
+        let variables =
+            SiteVariables::parse("1=content_type,2=content_keywords,3=missing").unwrap();
+        let parameters = parameters("123456", Some("abc"), &page_event(), &variables).unwrap();
+        assert_eq!(
+            parameters,
+            [
+                ("s", "123456"),
+                ("idclient", "abc"),
+                ("ts", "1714571100000"),
+                ("p", "home"),
+                ("x1", "article"),
+                ("x2", "a,b"),
+                ("r", "1920x1080"),
+                ("hl", "15x45x0"),
+                ("lng", "en-GB"),
+                ("ref", "https://www.google.com/?q=a&b"),
+            ]
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+        );
+    }
+
+    #[test]
+    fn click_hit() {
+        let mut event = page_event();
+        event.name = "add_to_cart".to_string();
+        event.data.previous_url = None;
+        event.data.user_id = Some("user-1".to_string());
+        let parameters = parameters("123456", None, &event, &SiteVariables::default()).unwrap();
+        assert_eq!(
+            parameters,
+            [
+                ("s", "123456"),
+                ("ts", "1714571100000"),
+                ("type", "click"),
+                ("click", "A"),
+                ("p", "add_to_cart"),
+                ("r", "1920x1080"),
+                ("hl", "15x45x0"),
+                ("lng", "en-GB"),
+                ("at", "user-1"),
+            ]
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+        );
+    }
+
+    #[test]
+    fn page_title_without_name() {
+        let mut event = page_event();
+        event.data.page_name = None;
+        let parameters = parameters("123456", None, &event, &SiteVariables::default()).unwrap();
+        assert!(parameters.contains(&("p".to_string(), "Home page".to_string())));
+    }
+
+    #[test]
+    fn denied_consent_hit() {
+        let mut event = page_event();
+        event.data.visitor_privacy_mode = "exempt".to_string();
+        event.data.user_id = Some("user-1".to_string());
+        let parameters =
+            parameters("123456", Some("abc"), &event, &SiteVariables::default()).unwrap();
+        assert!(!parameters
+            .iter()
+            .any(|(key, _)| key == "idclient" || key == "at"));
+    }
+
+    #[test]
+    fn optout_hit() {
+        let mut event = page_event();
+        event.data.visitor_privacy_mode = "optout".to_string();
+        event.data.user_id = Some("user-1".to_string());
+        let parameters =
+            parameters("123456", Some("abc"), &event, &SiteVariables::default()).unwrap();
+        assert!(!parameters
+            .iter()
+            .any(|(key, _)| key == "idclient" || key == "at"));
+    }
+}
@@ -1,5 +1,30 @@
+mod bot;
+mod campaign;
+mod content;
+mod device;
+mod endpoint;
+mod error;
+mod error_page;
+mod geo;
+mod headers;
+mod ip;
+mod legacy_hit;
+mod onsite_ad;
+mod paywall;
 mod piano_payload;
+mod routing;
+mod settings;
+mod time;
+// with tz-full, the compact table is only compiled for the tests comparing it to the full database
+#[cfg(any(not(feature = "tz-full"), test))]
+mod tz;
+mod url;
+mod user_agent;
+mod wildcard;
 
+use crate::endpoint::Protocol;
+use crate::error::PianoError;
+use crate::onsite_ad::OnsiteAd;
 use crate::piano_payload::parse_value;
 use exports::edgee::components::data_collection::Consent;
 use exports::edgee::components::data_collection::Data;
@@ -9,7 +34,9 @@ use exports::edgee::components::data_collection::Event;
 use exports::edgee::components::data_collection::Guest;
 use piano_payload::PianoEvent;
 use piano_payload::PianoPayload;
+use settings::PianoSettings;
 use std::vec;
+use url::UrlBuilder;
 wit_bindgen::generate!({world: "data-collection", path: ".edgee/wit", generate_all});
 
 export!(PianoComponent);
@@ -19,16 +46,11 @@ struct PianoComponent;
 impl Guest for PianoComponent {
     fn page(edgee_event: Event, settings: Dict) -> Result<EdgeeRequest, String> {
         if let Data::Page(ref data) = edgee_event.data {
-            let mut payload =
-                PianoPayload::new(&edgee_event, settings).map_err(|e| e.to_string())?;
+            let settings = PianoSettings::parse(&settings).map_err(PianoError::config)?;
+            let mut payload = PianoPayload::new("page.display", &edgee_event, &settings)?;
 
             // page_view event
-            let mut event = PianoEvent::new(
-                "page.display",
-                &edgee_event,
-                payload.collect_utm_as_properties,
-            )
-            .map_err(|e| e.to_string())?;
+            let mut event = PianoEvent::new("page.display", &edgee_event, &payload)?;
 
             if !data.name.is_empty() {
                 event.data.page_name = Some(data.name.clone());
@@ -41,7 +63,7 @@ impl Guest for PianoComponent {
             event.data.content_keywords = Some(data.keywords.clone());
 
             // event_url_full
-            if edgee_event.consent.is_some() && edgee_event.consent.unwrap() == Consent::Granted {
+            if edgee_event.consent == Some(Consent::Granted) {
                 event.data.event_url_full = Some(data.url.clone());
             } else {
                 let url_without_qs = edgee_event
@@ -57,14 +79,18 @@ impl Guest for PianoComponent {
 
             event.data.previous_url = Some(data.referrer.clone());
 
-            event.data.has_access = Some("anon".to_string());
+            event.data.has_access = Some(
+                paywall::has_access_from_user(&edgee_event.context.user.properties)
+                    .unwrap_or_else(|| "anon".to_string()),
+            );
 
             // add custom page properties
             if !data.properties.is_empty() {
                 for (key, value) in data.properties.clone().iter() {
-                    if key == "has_access" {
-                        event.data.has_access = Some(value.clone());
-                    } else {
+                    if !device::apply_viewport_property(key, value, &mut event.data)
+                        && !paywall::apply_property(key, value, &mut event.data)
+                        && !payload.content_mapping.apply(key, value, &mut event.data)
+                    {
                         event
                             .data
                             .additional_fields
@@ -73,68 +99,144 @@ impl Guest for PianoComponent {
                 }
             }
 
+            // error pages
+            if let Some(status_code) = error_page::detect(data, &payload.error_page_url_patterns) {
+                let page = event
+                    .data
+                    .page
+                    .clone()
+                    .or_else(|| event.data.page_name.clone())
+                    .unwrap_or_else(|| data.path.clone());
+                event.data.page = Some(error_page::page_name(status_code, &page));
+                event.data.is_error_page = Some(true);
+            }
+
             payload.events.push(event);
 
-            Ok(build_edgee_request(payload))
+            build_edgee_request(payload).map_err(String::from)
         } else {
-            Err("Missing page data".to_string())
+            Err(PianoError::MissingData("Missing page data".to_string()).into())
         }
     }
 
     fn track(edgee_event: Event, settings: Dict) -> Result<EdgeeRequest, String> {
         if let Data::Track(ref data) = edgee_event.data {
             if data.name.is_empty() {
-                return Err("Missing event name".to_string());
+                return Err(PianoError::MissingData("Missing event name".to_string()).into());
             }
 
-            let mut payload =
-                PianoPayload::new(&edgee_event, settings).map_err(|e| e.to_string())?;
+            let settings = PianoSettings::parse(&settings).map_err(PianoError::config)?;
+
+            // publisher and self-promotion events are renamed to their Piano onsite ads equivalent,
+            // paywall and subscription events to their Piano standard event
+            let onsite_ad = OnsiteAd::from_event_name(data.name.as_str());
+            let paywall_event = paywall::event_name(data.name.as_str());
+            let name = match (onsite_ad, paywall_event) {
+                (Some(ref onsite_ad), _) => onsite_ad.event_name(),
+                (None, Some(paywall_event)) => paywall_event,
+                (None, None) => data.name.as_str(),
+            };
+
+            // routing rules match the renamed event
+            let mut payload = PianoPayload::new(name, &edgee_event, &settings)?;
 
             // event
-            let mut event = PianoEvent::new(
-                data.name.as_str(),
-                &edgee_event,
-                payload.collect_utm_as_properties,
-            )
-            .map_err(|e| e.to_string())?;
+            let mut event = PianoEvent::new(name, &edgee_event, &payload)?;
 
             // add custom page properties
             if !data.properties.is_empty() {
                 for (key, value) in data.properties.clone().iter() {
-                    event
-                        .data
-                        .additional_fields
-                        .insert(key.clone(), parse_value(value));
+                    if device::apply_viewport_property(key, value, &mut event.data) {
+                        continue;
+                    }
+                    if paywall_event.is_some()
+                        && paywall::apply_property(key, value, &mut event.data)
+                    {
+                        continue;
+                    }
+                    let key = match onsite_ad {
+                        Some(_) => OnsiteAd::property_name(key),
+                        None => key.clone(),
+                    };
+                    event.data.additional_fields.insert(key, parse_value(value));
                 }
             }
 
+            if let Some(onsite_ad) = onsite_ad {
+                event.data.additional_fields.insert(
+                    "onsitead_type".to_string(),
+                    serde_json::Value::String(onsite_ad.type_label().to_string()),
+                );
+            }
+
             payload.events.push(event);
 
-            Ok(build_edgee_request(payload))
+            build_edgee_request(payload).map_err(String::from)
         } else {
-            Err("Missing track data".to_string())
+            Err(PianoError::MissingData("Missing track data".to_string()).into())
         }
     }
 
-    fn user(_edgee_event: Event, _settings: Dict) -> Result<EdgeeRequest, String> {
-        Err("User event not mapped to Piano Analytics".to_string())
+    fn user(_edgee_event: Event, settings: Dict) -> Result<EdgeeRequest, String> {
+        PianoSettings::parse(&settings).map_err(PianoError::config)?;
+        Err(PianoError::Unsupported("User event not mapped to Piano Analytics".to_string()).into())
     }
 }
 
-fn build_edgee_request(piano_payload: PianoPayload) -> EdgeeRequest {
-    let mut headers = vec![];
-    headers.push((String::from("content-type"), String::from("text/plain")));
+fn build_edgee_request(piano_payload: PianoPayload) -> Result<EdgeeRequest, PianoError> {
+    let url = UrlBuilder::new(
+        piano_payload.collection_scheme,
+        &piano_payload.collection_domain,
+        &piano_payload.collection_path,
+    );
+    // no idclient parameter without edgee id
+    let id_client = if piano_payload.id_client.is_empty() {
+        None
+    } else {
+        Some(piano_payload.id_client.as_str())
+    };
+
+    // Analytics Suite 2 hits are GET requests, one per event
+    if piano_payload.protocol == Protocol::As2 {
+        let event = piano_payload
+            .events
+            .first()
+            .ok_or_else(|| PianoError::MissingData("Missing event".to_string()))?;
+        let parameters = legacy_hit::parameters(
+            &piano_payload.site_id,
+            id_client,
+            event,
+            &piano_payload.as2_site_variables,
+        )?;
+        return Ok(EdgeeRequest {
+            method: exports::edgee::components::data_collection::HttpMethod::Get,
+            url: url.parameters(parameters).build(),
+            headers: piano_payload.headers.clone(),
+            forward_client_headers: piano_payload.forward_client_headers,
+            body: String::new(),
+        });
+    }
 
-    EdgeeRequest {
+    let mut headers = vec![(
+        String::from("content-type"),
+        piano_payload.content_type.as_str().to_string(),
+    )];
+    for (name, value) in &piano_payload.headers {
+        headers::set(&mut headers, name, value);
+    }
+    let body = serde_json::to_string(&piano_payload).map_err(|e| {
+        PianoError::Serialization(format!("Failed to serialize the payload: {}", e))
+    })?;
+    Ok(EdgeeRequest {
         method: exports::edgee::components::data_collection::HttpMethod::Post,
-        url: format!(
-            "https://{}/event?s={}&idclient={}",
-            piano_payload.collection_domain, piano_payload.site_id, piano_payload.id_client
-        ),
+        url: url
+            .parameter("s", &piano_payload.site_id)
+            .optional_parameter("idclient", id_client)
+            .build(),
         headers,
-        forward_client_headers: true,
-        body: serde_json::to_string(&piano_payload).unwrap(),
-    }
+        forward_client_headers: piano_payload.forward_client_headers,
+        body,
+    })
 }
 
 #[cfg(test)]
@@ -305,7 +407,7 @@ mod tests {
 
     fn sample_settings() -> Vec<(String, String)> {
         vec![
-            ("piano_site_id".to_string(), "abc".to_string()),
+            ("piano_site_id".to_string(), "123456".to_string()),
             (
                 "piano_collection_domain".to_string(),
                 sample_collection_domain(),
@@ -339,99 +441,302 @@ mod tests {
     }
 
     #[test]
-    fn page_without_consent() {
-        let event = sample_page_event(
-            None,
+    fn page_with_content_properties() {
+        let mut event = sample_page_event(
+            Some(Consent::Granted),
             "abc".to_string(),
             "fr".to_string(),
             "CET".to_string(),
             true,
         );
+        if let Data::Page(ref mut data) = event.data {
+            data.properties = vec![
+                ("article_id".to_string(), "42".to_string()),
+                ("author".to_string(), "Jane Doe".to_string()),
+                (
+                    "published_at".to_string(),
+                    "2024-05-01 08:30:00".to_string(),
+                ),
+                ("section".to_string(), "sports".to_string()),
+                ("prop1".to_string(), "value1".to_string()),
+            ];
+        }
         let settings = sample_settings();
         let result = PianoComponent::page(event, settings);
+        assert_eq!(result.is_err(), false);
+
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let data = &body["events"][0]["data"];
+        assert_eq!(data["content_id"], "42");
+        assert_eq!(data["content_author"], "Jane Doe");
+        assert_eq!(
+            data["content_publication_date"],
+            "2024-05-01T08:30:00+00:00"
+        );
+        assert_eq!(data["content_section"], "sports");
+        assert_eq!(data["prop1"], "value1");
+        assert!(data.get("article_id").is_none());
+        assert!(data.get("author").is_none());
+    }
 
+    #[test]
+    fn page_with_custom_content_mapping() {
+        let mut event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        if let Data::Page(ref mut data) = event.data {
+            data.properties = vec![
+                ("article_id".to_string(), "42".to_string()),
+                ("kind".to_string(), "video".to_string()),
+            ];
+        }
+        let mut settings = sample_settings();
+        settings.push((
+            "piano_content_mapping".to_string(),
+            "content_type=kind".to_string(),
+        ));
+        let result = PianoComponent::page(event, settings);
         assert_eq!(result.is_err(), false);
-        let edgee_request = result.unwrap();
-        assert_eq!(edgee_request.method, HttpMethod::Post);
-        assert!(!edgee_request.body.is_empty());
+
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let data = &body["events"][0]["data"];
+        assert_eq!(data["content_type"], "video");
+        assert_eq!(data["article_id"], 42);
+        assert!(data.get("content_id").is_none());
     }
 
     #[test]
-    fn page_with_dashed_locale() {
+    fn page_with_invalid_content_mapping_fails() {
         let event = sample_page_event(
             None,
             "abc".to_string(),
-            "fr-fr".to_string(),
+            "fr".to_string(),
             "CET".to_string(),
             true,
         );
-        let settings = sample_settings();
+        let mut settings = sample_settings();
+        settings.push((
+            "piano_content_mapping".to_string(),
+            "content_foo=bar".to_string(),
+        ));
         let result = PianoComponent::page(event, settings);
+        assert_eq!(result.is_err(), true);
+    }
 
+    #[test]
+    fn page_has_access_from_user_properties() {
+        let mut event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        if let Data::Page(ref mut data) = event.data {
+            data.properties = vec![];
+        }
+        event
+            .context
+            .user
+            .properties
+            .push(("subscription_status".to_string(), "active".to_string()));
+        let settings = sample_settings();
+        let result = PianoComponent::page(event, settings);
         assert_eq!(result.is_err(), false);
-        let edgee_request = result.unwrap();
-        assert_eq!(edgee_request.method, HttpMethod::Post);
-        assert!(!edgee_request.body.is_empty());
+
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let data = &body["events"][0]["data"];
+        assert_eq!(data["has_access"], "subscriber");
+        assert_eq!(data["user_subscription_status"], "active");
     }
 
     #[test]
-    fn page_with_empty_locale() {
-        let event = sample_page_event(
+    fn page_has_access_defaults_to_anon() {
+        let mut event = sample_page_event(
             None,
-            Uuid::new_v4().to_string(),
-            "".to_string(),
+            "abc".to_string(),
+            "fr".to_string(),
             "CET".to_string(),
             true,
         );
+        if let Data::Page(ref mut data) = event.data {
+            data.properties = vec![];
+        }
+        let settings = sample_settings();
+        let result = PianoComponent::page(event, settings);
+        assert_eq!(result.is_err(), false);
+
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        assert_eq!(body["events"][0]["data"]["has_access"], "anon");
+    }
 
+    #[test]
+    fn page_error_from_status_code() {
+        let mut event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        if let Data::Page(ref mut data) = event.data {
+            data.properties = vec![("status_code".to_string(), "404".to_string())];
+        }
         let settings = sample_settings();
         let result = PianoComponent::page(event, settings);
+        assert_eq!(result.is_err(), false);
+
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let data = &body["events"][0]["data"];
+        assert_eq!(data["page"], "error_404::page title");
+        assert_eq!(data["is_error_page"], true);
+    }
 
+    #[test]
+    fn page_error_from_url_patterns() {
+        let mut event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        if let Data::Page(ref mut data) = event.data {
+            data.title = "".to_string();
+            data.path = "/errors/missing".to_string();
+        }
+        let mut settings = sample_settings();
+        settings.push((
+            "piano_error_page_url_patterns".to_string(),
+            "/errors/*, /404".to_string(),
+        ));
+        let result = PianoComponent::page(event, settings);
         assert_eq!(result.is_err(), false);
-        let edgee_request = result.unwrap();
-        assert_eq!(edgee_request.method, HttpMethod::Post);
-        assert!(!edgee_request.body.is_empty());
+
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let data = &body["events"][0]["data"];
+        assert_eq!(data["page"], "error_404::page name");
+        assert_eq!(data["is_error_page"], true);
     }
 
     #[test]
-    fn page_with_empty_timezone() {
+    fn page_not_an_error() {
         let event = sample_page_event(
-            None,
-            Uuid::new_v4().to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
             "fr".to_string(),
-            "".to_string(),
+            "CET".to_string(),
             true,
         );
+        let settings = sample_settings();
+        let result = PianoComponent::page(event, settings);
+        assert_eq!(result.is_err(), false);
+
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let data = &body["events"][0]["data"];
+        assert_eq!(data["page"], "page title");
+        assert!(data.get("is_error_page").is_none());
+    }
 
+    #[test]
+    fn page_visit_properties() {
+        let mut event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        event.timestamp = 1_700_003_600;
+        event.context.session.session_id = "session-2".to_string();
+        event.context.session.previous_session_id = "session-1".to_string();
+        event.context.session.first_seen = 1_600_000_000;
+        event.context.session.last_seen = 1_700_000_000;
         let settings = sample_settings();
         let result = PianoComponent::page(event, settings);
+        assert_eq!(result.is_err(), false);
 
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let data = &body["events"][0]["data"];
+        assert_eq!(data["visit_id"], "session-2");
+        assert_eq!(data["visit_previous_id"], "session-1");
+        assert_eq!(data["visit_count"], 2);
+        assert_eq!(data["visitor_new"], false);
+        assert_eq!(data["visit_time_since_last"], 3600);
+    }
+
+    #[test]
+    fn page_visit_properties_without_consent() {
+        let mut event = sample_page_event(
+            Some(Consent::Denied),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            false,
+        );
+        event.context.session.session_count = 1;
+        let settings = sample_settings();
+        let result = PianoComponent::page(event, settings);
         assert_eq!(result.is_err(), false);
-        let edgee_request = result.unwrap();
-        assert_eq!(edgee_request.method, HttpMethod::Post);
-        assert!(!edgee_request.body.is_empty());
+
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let data = &body["events"][0]["data"];
+        assert!(data.get("visit_id").is_none());
+        assert!(data.get("visit_previous_id").is_none());
+        assert!(data.get("visit_time_since_last").is_none());
+        assert_eq!(data["visit_count"], 1);
+        assert_eq!(data["visitor_new"], true);
     }
 
     #[test]
-    fn page_not_session_start() {
+    fn page_geo_properties() {
+        let event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push(("piano_geo_granularity".to_string(), "region".to_string()));
+        let result = PianoComponent::page(event, settings);
+        assert_eq!(result.is_err(), false);
+
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let data = &body["events"][0]["data"];
+        assert_eq!(data["geo_continent"], "Europe");
+        assert_eq!(data["geo_country"], "France");
+        assert_eq!(data["geo_country_code_alpha2"], "FR");
+        assert_eq!(data["geo_region"], "West Europe");
+        assert!(data.get("geo_city").is_none());
+    }
+
+    #[test]
+    fn page_geo_properties_without_consent() {
         let event = sample_page_event(
             None,
-            Uuid::new_v4().to_string(),
-            "".to_string(),
+            "abc".to_string(),
+            "fr".to_string(),
             "CET".to_string(),
-            false,
+            true,
         );
         let settings = sample_settings();
         let result = PianoComponent::page(event, settings);
-
         assert_eq!(result.is_err(), false);
-        let edgee_request = result.unwrap();
-        assert_eq!(edgee_request.method, HttpMethod::Post);
-        assert!(!edgee_request.body.is_empty());
+
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let data = &body["events"][0]["data"];
+        assert_eq!(data["geo_country_code_alpha2"], "FR");
+        assert!(data.get("geo_region").is_none());
+        assert!(data.get("geo_city").is_none());
     }
 
     #[test]
-    fn page_without_site_id_fails() {
+    fn page_with_invalid_geo_granularity_fails() {
         let event = sample_page_event(
             None,
             "abc".to_string(),
@@ -439,13 +744,14 @@ mod tests {
             "CET".to_string(),
             true,
         );
-        let settings: Vec<(String, String)> = vec![]; // empty
-        let result = PianoComponent::page(event, settings); // this should panic!
+        let mut settings = sample_settings();
+        settings.push(("piano_geo_granularity".to_string(), "street".to_string()));
+        let result = PianoComponent::page(event, settings);
         assert_eq!(result.is_err(), true);
     }
 
     #[test]
-    fn page_without_collection_domain_fails() {
+    fn page_forwards_client_headers_by_default() {
         let event = sample_page_event(
             None,
             "abc".to_string(),
@@ -453,29 +759,879 @@ mod tests {
             "CET".to_string(),
             true,
         );
-        let settings: Vec<(String, String)> = vec![
-            ("piano_site_id".to_string(), "abc".to_string()), // only site ID
+        let settings = sample_settings();
+        let edgee_request = PianoComponent::page(event, settings).unwrap();
+        assert_eq!(edgee_request.forward_client_headers, true);
+        assert_eq!(
+            edgee_request.headers,
+            vec![("content-type".to_string(), "text/plain".to_string())]
+        );
+    }
+
+    #[test]
+    fn page_sends_client_ip_with_consent() {
+        let event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push(("piano_send_client_ip".to_string(), "true".to_string()));
+        let edgee_request = PianoComponent::page(event, settings).unwrap();
+        assert_eq!(edgee_request.forward_client_headers, false);
+        assert_eq!(
+            edgee_request.headers,
+            vec![
+                ("content-type".to_string(), "text/plain".to_string()),
+                ("x-forwarded-for".to_string(), "192.168.0.1".to_string()),
+                ("user-agent".to_string(), "Chrome".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn page_sends_truncated_client_ip_without_consent() {
+        let event = sample_page_event(
+            Some(Consent::Denied),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push(("piano_send_client_ip".to_string(), "true".to_string()));
+        let edgee_request = PianoComponent::page(event, settings).unwrap();
+        assert_eq!(edgee_request.forward_client_headers, false);
+        assert_eq!(
+            edgee_request.headers,
+            vec![
+                ("content-type".to_string(), "text/plain".to_string()),
+                ("x-forwarded-for".to_string(), "192.168.0.0".to_string()),
+                ("user-agent".to_string(), "Chrome".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn page_custom_endpoint() {
+        let event = sample_page_event(
+            None,
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let settings = vec![
+            ("piano_site_id".to_string(), "123456".to_string()),
+            (
+                "piano_collection_domain".to_string(),
+                "localhost:8080".to_string(),
+            ),
+            ("piano_collection_scheme".to_string(), "http".to_string()),
+            ("piano_collection_path".to_string(), "/collect".to_string()),
         ];
-        let result = PianoComponent::page(event, settings); // this should panic!
-        assert_eq!(result.is_err(), true);
+        let edgee_request = PianoComponent::page(event.clone(), settings).unwrap();
+        assert_eq!(
+            edgee_request.url,
+            "http://localhost:8080/collect?s=123456&idclient=abc"
+        );
     }
 
     #[test]
-    fn track_with_consent() {
-        let event = sample_track_event(
-            "event-name".to_string(),
+    fn page_as2_hit() {
+        let event = sample_page_event(
             Some(Consent::Granted),
             "abc".to_string(),
             "fr".to_string(),
             "CET".to_string(),
             true,
         );
-        let settings = sample_settings();
-        let result = PianoComponent::track(event, settings);
-        assert_eq!(result.clone().is_err(), false);
-        let edgee_request = result.unwrap();
-        assert_eq!(edgee_request.method, HttpMethod::Post);
-        assert!(!edgee_request.body.is_empty());
+        let mut settings = sample_settings();
+        settings.push(("piano_protocol".to_string(), "as2".to_string()));
+        settings.push((
+            "piano_as2_site_variables".to_string(),
+            "1=has_access".to_string(),
+        ));
+        let edgee_request = PianoComponent::page(event, settings.clone()).unwrap();
+        assert_eq!(edgee_request.method, HttpMethod::Get);
+        assert!(edgee_request.body.is_empty());
+        assert!(edgee_request.headers.is_empty());
+        assert_eq!(
+            edgee_request.url,
+            format!(
+                "https://{}/hit.xiti?s=123456&idclient=abc&ts=123&p=page%20name&x1=true\
+                 &r=1024x768&hl=1x0x0&lng=fr&at=123&ref=https%3A%2F%2Fexample.com%2Fanother-page",
+                sample_collection_domain()
+            )
+        );
+
+        // visitors denying consent are not identified
+        let event = sample_page_event(
+            Some(Consent::Denied),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let edgee_request = PianoComponent::page(event, settings).unwrap();
+        assert!(!edgee_request.url.contains("idclient="));
+        assert!(!edgee_request.url.contains("&at="));
+    }
+
+    #[test]
+    fn page_without_edgee_id() {
+        let event = sample_page_event(
+            Some(Consent::Granted),
+            "".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let edgee_request = PianoComponent::page(event, sample_settings()).unwrap();
+        assert_eq!(
+            edgee_request.url,
+            format!("https://{}/event?s=123456", sample_collection_domain())
+        );
+    }
+
+    #[test]
+    fn errors_start_with_code() {
+        let event = sample_page_event(
+            None,
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.retain(|(key, _)| key != "piano_site_id");
+        assert_eq!(
+            PianoComponent::page(event.clone(), settings).err(),
+            Some("[PIANO_CONFIG] Missing piano site id".to_string())
+        );
+
+        let mut settings = sample_settings();
+        settings.push(("piano_protocol".to_string(), "xiti".to_string()));
+        assert!(PianoComponent::page(event.clone(), settings)
+            .err()
+            .unwrap()
+            .starts_with("[PIANO_CONFIG] Invalid piano_protocol setting"));
+
+        let track_event = sample_track_event(
+            "".to_string(),
+            None,
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        assert_eq!(
+            PianoComponent::track(track_event, sample_settings()).err(),
+            Some("[PIANO_MISSING_DATA] Missing event name".to_string())
+        );
+    }
+
+    #[test]
+    fn page_json_content_type() {
+        let event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let text_request = PianoComponent::page(event.clone(), sample_settings()).unwrap();
+        let mut settings = sample_settings();
+        settings.push((
+            "piano_content_type".to_string(),
+            "application/json".to_string(),
+        ));
+        let json_request = PianoComponent::page(event, settings).unwrap();
+
+        assert_eq!(
+            json_request.headers[0],
+            ("content-type".to_string(), "application/json".to_string())
+        );
+        // same payload, whatever the content type
+        let text_body: serde_json::Value = serde_json::from_str(&text_request.body).unwrap();
+        let json_body: serde_json::Value = serde_json::from_str(&json_request.body).unwrap();
+        assert_eq!(text_body, json_body);
+    }
+
+    #[test]
+    fn page_with_non_finite_number_properties() {
+        let mut event = sample_page_event(
+            None,
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        if let Data::Page(ref mut data) = event.data {
+            data.properties = vec![
+                ("ratio".to_string(), "inf".to_string()),
+                ("score".to_string(), "NaN".to_string()),
+            ];
+        }
+        let edgee_request = PianoComponent::page(event, sample_settings()).unwrap();
+        let body: serde_json::Value = serde_json::from_str(&edgee_request.body).unwrap();
+        let data = &body["events"][0]["data"];
+        assert_eq!(data["ratio"], "inf");
+        assert_eq!(data["score"], "NaN");
+    }
+
+    #[test]
+    fn out_of_range_timestamp_fails() {
+        let mut event = sample_page_event(
+            None,
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        event.timestamp = i64::MAX;
+        assert_eq!(
+            PianoComponent::page(event, sample_settings()).err(),
+            Some(format!(
+                "[PIANO_VALIDATION] Invalid event timestamp: {}",
+                i64::MAX
+            ))
+        );
+
+        let mut event = sample_track_event(
+            "event-name".to_string(),
+            None,
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        event.timestamp = i64::MIN;
+        assert!(PianoComponent::track(event, sample_settings()).is_err());
+    }
+
+    #[test]
+    fn page_bot_filtering() {
+        let mut event = sample_page_event(
+            None,
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        event.context.client.user_agent =
+            "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)".to_string();
+
+        // off by default
+        let edgee_request = PianoComponent::page(event.clone(), sample_settings()).unwrap();
+        assert!(!edgee_request.body.contains("\"is_bot\""));
+
+        let mut settings = sample_settings();
+        settings.push(("piano_bot_filtering".to_string(), "tag".to_string()));
+        let edgee_request = PianoComponent::page(event.clone(), settings).unwrap();
+        assert!(edgee_request.body.contains("\"is_bot\":true"));
+
+        let mut settings = sample_settings();
+        settings.push(("piano_bot_filtering".to_string(), "drop".to_string()));
+        assert_eq!(
+            PianoComponent::page(event, settings).err(),
+            Some("[PIANO_FILTERED] Event from a bot, dropped".to_string())
+        );
+    }
+
+    #[test]
+    fn page_bot_extra_patterns() {
+        let event = sample_page_event(
+            None,
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push(("piano_bot_filtering".to_string(), "drop".to_string()));
+        assert!(PianoComponent::page(event.clone(), settings.clone()).is_ok());
+
+        settings.push(("piano_bot_patterns".to_string(), "chrom*".to_string()));
+        assert!(PianoComponent::page(event, settings).is_err());
+    }
+
+    #[test]
+    fn page_forwarded_and_custom_headers() {
+        let event = sample_page_event(
+            Some(Consent::Denied),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push((
+            "piano_forwarded_headers".to_string(),
+            "accept-language,x-forwarded-for".to_string(),
+        ));
+        settings.push((
+            "piano_custom_headers".to_string(),
+            "Authorization=Bearer abc,Content-Type=application/json".to_string(),
+        ));
+        let edgee_request = PianoComponent::page(event, settings).unwrap();
+        assert_eq!(edgee_request.forward_client_headers, false);
+        assert_eq!(
+            edgee_request.headers,
+            vec![
+                ("content-type".to_string(), "application/json".to_string()),
+                ("accept-language".to_string(), "fr".to_string()),
+                ("x-forwarded-for".to_string(), "192.168.0.0".to_string()),
+                ("authorization".to_string(), "Bearer abc".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn page_without_client_headers_forwarding() {
+        let event = sample_page_event(
+            None,
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push((
+            "piano_forward_client_headers".to_string(),
+            "false".to_string(),
+        ));
+        let edgee_request = PianoComponent::page(event, settings).unwrap();
+        assert_eq!(edgee_request.forward_client_headers, false);
+        assert_eq!(
+            edgee_request.headers,
+            vec![("content-type".to_string(), "text/plain".to_string())]
+        );
+    }
+
+    #[test]
+    fn page_screen_and_viewport() {
+        let mut event = sample_page_event(
+            None,
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        if let Data::Page(ref mut data) = event.data {
+            data.properties = vec![
+                ("viewport_width".to_string(), "1000".to_string()),
+                ("viewport_height".to_string(), "600".to_string()),
+            ];
+        }
+        let settings = sample_settings();
+        let result = PianoComponent::page(event, settings);
+        assert_eq!(result.is_err(), false);
+
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let data = &body["events"][0]["data"];
+        assert_eq!(data["device_screen_width"], 1024);
+        assert_eq!(data["device_screen_height"], 768);
+        assert_eq!(data["device_display_width"], 1000);
+        assert_eq!(data["device_display_height"], 600);
+        assert_eq!(data["device_pixel_ratio"], 2.0);
+        assert!(data.get("viewport_width").is_none());
+    }
+
+    #[test]
+    fn track_without_viewport() {
+        let event = sample_track_event(
+            "event-name".to_string(),
+            None,
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let settings = sample_settings();
+        let result = PianoComponent::track(event, settings);
+        assert_eq!(result.is_err(), false);
+
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let data = &body["events"][0]["data"];
+        assert_eq!(data["device_screen_width"], 1024);
+        assert!(data.get("device_display_width").is_none());
+        assert!(data.get("device_display_height").is_none());
+    }
+
+    #[test]
+    fn page_device_time() {
+        let mut event = sample_page_event(
+            None,
+            "abc".to_string(),
+            "fr".to_string(),
+            "Europe/Paris".to_string(),
+            true,
+        );
+        // 2024-03-31T01:30:00Z, just after the switch to summer time in Paris
+        event.timestamp = 1_711_848_600;
+        event.timestamp_millis = 1_711_848_600_000;
+        let settings = sample_settings();
+        let result = PianoComponent::page(event, settings);
+        assert_eq!(result.is_err(), false);
+
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let data = &body["events"][0]["data"];
+        assert_eq!(data["device_hour"], 3);
+        assert_eq!(data["device_local_hour"], 3);
+        assert_eq!(data["device_local_date"], "2024-03-31");
+        assert_eq!(data["device_local_weekday"], 7);
+        assert_eq!(data["device_utc_offset"], 120);
+        assert_eq!(data["device_timestamp_utc"], 1_711_848_600_000i64);
+    }
+
+    #[test]
+    fn page_device_time_with_unknown_timezone() {
+        let mut event = sample_page_event(
+            None,
+            "abc".to_string(),
+            "fr".to_string(),
+            "Nowhere/Unknown".to_string(),
+            true,
+        );
+        event.timestamp = 1_711_848_600;
+        let settings = sample_settings();
+        let result = PianoComponent::page(event, settings);
+        assert_eq!(result.is_err(), false);
+
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let data = &body["events"][0]["data"];
+        assert_eq!(data["device_hour"], 1);
+        assert_eq!(data["device_local_hour"], 1);
+        assert_eq!(data["device_utc_offset"], 0);
+    }
+
+    #[test]
+    fn page_without_consent() {
+        let event = sample_page_event(
+            None,
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let settings = sample_settings();
+        let result = PianoComponent::page(event, settings);
+
+        assert_eq!(result.is_err(), false);
+        let edgee_request = result.unwrap();
+        assert_eq!(edgee_request.method, HttpMethod::Post);
+        assert!(!edgee_request.body.is_empty());
+    }
+
+    #[test]
+    fn page_with_dashed_locale() {
+        let event = sample_page_event(
+            None,
+            "abc".to_string(),
+            "fr-fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let settings = sample_settings();
+        let result = PianoComponent::page(event, settings);
+
+        assert_eq!(result.is_err(), false);
+        let edgee_request = result.unwrap();
+        assert_eq!(edgee_request.method, HttpMethod::Post);
+        assert!(!edgee_request.body.is_empty());
+    }
+
+    #[test]
+    fn page_with_empty_locale() {
+        let event = sample_page_event(
+            None,
+            Uuid::new_v4().to_string(),
+            "".to_string(),
+            "CET".to_string(),
+            true,
+        );
+
+        let settings = sample_settings();
+        let result = PianoComponent::page(event, settings);
+
+        assert_eq!(result.is_err(), false);
+        let edgee_request = result.unwrap();
+        assert_eq!(edgee_request.method, HttpMethod::Post);
+        assert!(!edgee_request.body.is_empty());
+    }
+
+    #[test]
+    fn page_with_empty_timezone() {
+        let event = sample_page_event(
+            None,
+            Uuid::new_v4().to_string(),
+            "fr".to_string(),
+            "".to_string(),
+            true,
+        );
+
+        let settings = sample_settings();
+        let result = PianoComponent::page(event, settings);
+
+        assert_eq!(result.is_err(), false);
+        let edgee_request = result.unwrap();
+        assert_eq!(edgee_request.method, HttpMethod::Post);
+        assert!(!edgee_request.body.is_empty());
+    }
+
+    #[test]
+    fn page_not_session_start() {
+        let event = sample_page_event(
+            None,
+            Uuid::new_v4().to_string(),
+            "".to_string(),
+            "CET".to_string(),
+            false,
+        );
+        let settings = sample_settings();
+        let result = PianoComponent::page(event, settings);
+
+        assert_eq!(result.is_err(), false);
+        let edgee_request = result.unwrap();
+        assert_eq!(edgee_request.method, HttpMethod::Post);
+        assert!(!edgee_request.body.is_empty());
+    }
+
+    #[test]
+    fn page_without_site_id_fails() {
+        let event = sample_page_event(
+            None,
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let settings: Vec<(String, String)> = vec![]; // empty
+        let result = PianoComponent::page(event, settings); // this should panic!
+        assert_eq!(result.is_err(), true);
+    }
+
+    #[test]
+    fn page_without_collection_domain_fails() {
+        let event = sample_page_event(
+            None,
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let settings: Vec<(String, String)> = vec![
+            ("piano_site_id".to_string(), "123456".to_string()), // only site ID
+        ];
+        let result = PianoComponent::page(event, settings); // this should panic!
+        assert_eq!(result.is_err(), true);
+    }
+
+    #[test]
+    fn track_with_consent() {
+        let event = sample_track_event(
+            "event-name".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let settings = sample_settings();
+        let result = PianoComponent::track(event, settings);
+        assert_eq!(result.clone().is_err(), false);
+        let edgee_request = result.unwrap();
+        assert_eq!(edgee_request.method, HttpMethod::Post);
+        assert!(!edgee_request.body.is_empty());
+    }
+
+    #[test]
+    fn track_onsite_ad_event() {
+        let mut event = sample_track_event(
+            "self_promotion_click".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        if let Data::Track(ref mut data) = event.data {
+            data.properties = vec![
+                ("creative".to_string(), "banner-1".to_string()),
+                ("campaign".to_string(), "summer".to_string()),
+                ("format".to_string(), "300x250".to_string()),
+                ("prop1".to_string(), "value1".to_string()),
+            ];
+        }
+        let settings = sample_settings();
+        let result = PianoComponent::track(event, settings);
+        assert_eq!(result.is_err(), false);
+
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let piano_event = &body["events"][0];
+        assert_eq!(piano_event["name"], "self_promotion.click");
+        assert_eq!(piano_event["data"]["onsitead_type"], "Self promotion");
+        assert_eq!(piano_event["data"]["onsitead_creation"], "banner-1");
+        assert_eq!(piano_event["data"]["onsitead_campaign"], "summer");
+        assert_eq!(piano_event["data"]["onsitead_format"], "300x250");
+        assert_eq!(piano_event["data"]["prop1"], "value1");
+        assert!(piano_event["data"].get("creative").is_none());
+    }
+
+    #[test]
+    fn track_paywall_event() {
+        let mut event = sample_track_event(
+            "subscription_started".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        if let Data::Track(ref mut data) = event.data {
+            data.properties = vec![
+                ("subscription_plan".to_string(), "yearly".to_string()),
+                ("is_premium".to_string(), "true".to_string()),
+                ("has_access".to_string(), "true".to_string()),
+                ("prop1".to_string(), "value1".to_string()),
+            ];
+        }
+        let settings = sample_settings();
+        let result = PianoComponent::track(event, settings);
+        assert_eq!(result.is_err(), false);
+
+        let body: serde_json::Value = serde_json::from_str(&result.unwrap().body).unwrap();
+        let piano_event = &body["events"][0];
+        assert_eq!(piano_event["name"], "subscription.start");
+        assert_eq!(piano_event["data"]["user_subscription_plan"], "yearly");
+        assert_eq!(piano_event["data"]["content_is_premium"], true);
+        assert_eq!(piano_event["data"]["has_access"], "true");
+        assert_eq!(piano_event["data"]["prop1"], "value1");
+        assert!(piano_event["data"].get("subscription_plan").is_none());
+    }
+
+    #[test]
+    fn track_routed_to_another_site() {
+        let event = sample_track_event(
+            "purchase".to_string(),
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push((
+            "piano_site_routing".to_string(),
+            "event:page.display=111,event:purchase&country:FR=222,country:FR=333".to_string(),
+        ));
+        let result = PianoComponent::track(event, settings);
+        assert_eq!(result.is_err(), false);
+        assert_eq!(
+            result.unwrap().url,
+            format!(
+                "https://{}/event?s=222&idclient=abc",
+                sample_collection_domain()
+            )
+        );
+    }
+
+    #[test]
+    fn track_renamed_event_routed() {
+        let mut settings = sample_settings();
+        settings.push((
+            "piano_site_routing".to_string(),
+            "event:publisher_click=111,event:publisher.click=222,event:paywall.display=333"
+                .to_string(),
+        ));
+        for (name, site_id) in [("Publisher_Click", "222"), ("paywall_displayed", "333")] {
+            let event = sample_track_event(
+                name.to_string(),
+                Some(Consent::Granted),
+                "abc".to_string(),
+                "fr".to_string(),
+                "CET".to_string(),
+                true,
+            );
+            let result = PianoComponent::track(event, settings.clone());
+            assert_eq!(
+                result.unwrap().url,
+                format!(
+                    "https://{}/event?s={}&idclient=abc",
+                    sample_collection_domain(),
+                    site_id
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn page_routed_by_hostname() {
+        let event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push((
+            "piano_site_routing".to_string(),
+            "host:shop.example.com=111,host:*example.com=222".to_string(),
+        ));
+        let result = PianoComponent::page(event, settings);
+        assert_eq!(result.is_err(), false);
+        assert_eq!(
+            result.unwrap().url,
+            format!(
+                "https://{}/event?s=222&idclient=abc",
+                sample_collection_domain()
+            )
+        );
+    }
+
+    #[test]
+    fn page_with_hostname_site() {
+        let event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push((
+            "piano_hostname_sites".to_string(),
+            "www.example.fr=111,example.com=222@other.pa-cd.com".to_string(),
+        ));
+        let result = PianoComponent::page(event, settings);
+        assert_eq!(result.is_err(), false);
+        assert_eq!(
+            result.unwrap().url,
+            "https://other.pa-cd.com/event?s=222&idclient=abc"
+        );
+    }
+
+    #[test]
+    fn page_with_hostname_site_without_default_site() {
+        let event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let settings = vec![
+            (
+                "piano_collection_domain".to_string(),
+                sample_collection_domain(),
+            ),
+            (
+                "piano_hostname_sites".to_string(),
+                "example.com=222".to_string(),
+            ),
+        ];
+        let result = PianoComponent::page(event, settings);
+        assert_eq!(result.is_err(), false);
+        assert_eq!(
+            result.unwrap().url,
+            format!(
+                "https://{}/event?s=222&idclient=abc",
+                sample_collection_domain()
+            )
+        );
+    }
+
+    #[test]
+    fn page_without_matching_hostname_site_nor_default_site_fails() {
+        let event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let settings = vec![
+            (
+                "piano_collection_domain".to_string(),
+                sample_collection_domain(),
+            ),
+            (
+                "piano_hostname_sites".to_string(),
+                "example.fr=222".to_string(),
+            ),
+        ];
+        let result = PianoComponent::page(event, settings);
+        assert_eq!(result.is_err(), true);
+    }
+
+    #[test]
+    fn page_with_hostname_site_and_routing() {
+        let event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push((
+            "piano_hostname_sites".to_string(),
+            "example.com=222@other.pa-cd.com".to_string(),
+        ));
+        settings.push((
+            "piano_site_routing".to_string(),
+            "country:FR=333".to_string(),
+        ));
+        let result = PianoComponent::page(event, settings);
+        assert_eq!(result.is_err(), false);
+        assert_eq!(
+            result.unwrap().url,
+            "https://other.pa-cd.com/event?s=333&idclient=abc"
+        );
+    }
+
+    #[test]
+    fn page_not_routed() {
+        let event = sample_page_event(
+            Some(Consent::Granted),
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push((
+            "piano_site_routing".to_string(),
+            "country:US=111".to_string(),
+        ));
+        let result = PianoComponent::page(event, settings);
+        assert_eq!(result.is_err(), false);
+        assert_eq!(
+            result.unwrap().url,
+            format!(
+                "https://{}/event?s=123456&idclient=abc",
+                sample_collection_domain()
+            )
+        );
+    }
+
+    #[test]
+    fn page_with_invalid_routing_fails() {
+        let event = sample_page_event(
+            None,
+            "abc".to_string(),
+            "fr".to_string(),
+            "CET".to_string(),
+            true,
+        );
+        let mut settings = sample_settings();
+        settings.push(("piano_site_routing".to_string(), "country=111".to_string()));
+        let result = PianoComponent::page(event, settings);
+        assert_eq!(result.is_err(), true);
     }
 
     #[test]
@@ -515,6 +1671,10 @@ mod tests {
                 .contains("not mapped"),
             true
         );
+        assert!(result
+            .err()
+            .unwrap()
+            .starts_with("[PIANO_UNSUPPORTED_EVENT]"));
     }
 
     #[test]
@@ -0,0 +1,148 @@
+// Onsite ads (publisher and self-promotion) events
+//
+// https://developers.atinternet-solutions.com/piano-analytics/data-collection/how-to-send-events/onsite-ads
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum OnsiteAdType {
+    Publisher,
+    SelfPromotion,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum OnsiteAdAction {
+    Impression,
+    Click,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct OnsiteAd {
+    pub ad_type: OnsiteAdType,
+    pub action: OnsiteAdAction,
+}
+
+// track properties sent by the ad-ops tagging, and their Piano counterparts
+const PROPERTY_MAPPING: [(&str, &str); 11] = [
+    ("advertiser", "onsitead_advertiser"),
+    ("campaign", "onsitead_campaign"),
+    ("category", "onsitead_category"),
+    ("creative", "onsitead_creation"),
+    ("creation", "onsitead_creation"),
+    ("format", "onsitead_format"),
+    ("placement", "onsitead_general_placement"),
+    ("general_placement", "onsitead_general_placement"),
+    ("detailed_placement", "onsitead_detailed_placement"),
+    ("url", "onsitead_url"),
+    ("variant", "onsitead_variant"),
+];
+
+impl OnsiteAd {
+    // accepts the Piano event names (publisher.impression, self_promotion.click, ...)
+    // as well as their snake/kebab case variants (publisher_impression, self-promotion-click, ...)
+    pub(crate) fn from_event_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase().replace(['-', ' '], "_");
+        let (ad_type, action) = name.rsplit_once(['.', '_'])?;
+
+        let ad_type = match ad_type {
+            "publisher" => OnsiteAdType::Publisher,
+            "self_promotion" | "selfpromotion" => OnsiteAdType::SelfPromotion,
+            _ => return None,
+        };
+        let action = match action {
+            "impression" => OnsiteAdAction::Impression,
+            "click" => OnsiteAdAction::Click,
+            _ => return None,
+        };
+
+        Some(Self { ad_type, action })
+    }
+
+    pub(crate) fn event_name(&self) -> &'static str {
+        match (self.ad_type, self.action) {
+            (OnsiteAdType::Publisher, OnsiteAdAction::Impression) => "publisher.impression",
+            (OnsiteAdType::Publisher, OnsiteAdAction::Click) => "publisher.click",
+            (OnsiteAdType::SelfPromotion, OnsiteAdAction::Impression) => {
+                "self_promotion.impression"
+            }
+            (OnsiteAdType::SelfPromotion, OnsiteAdAction::Click) => "self_promotion.click",
+        }
+    }
+
+    // value of the onsitead_type property
+    pub(crate) fn type_label(&self) -> &'static str {
+        match self.ad_type {
+            OnsiteAdType::Publisher => "Publisher",
+            OnsiteAdType::SelfPromotion => "Self promotion",
+        }
+    }
+
+    // rename a track property to its onsitead_* equivalent,
+    // unknown properties are kept as they are
+    pub(crate) fn property_name(key: &str) -> String {
+        PROPERTY_MAPPING
+            .iter()
+            .find(|(from, _)| *from == key)
+            .map(|(_, to)| to.to_string())
+            .unwrap_or_else(|| key.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_event_name_piano_names() {
+        let ad = OnsiteAd::from_event_name("publisher.impression").unwrap();
+        assert_eq!(ad.ad_type, OnsiteAdType::Publisher);
+        assert_eq!(ad.action, OnsiteAdAction::Impression);
+
+        let ad = OnsiteAd::from_event_name("self_promotion.click").unwrap();
+        assert_eq!(ad.ad_type, OnsiteAdType::SelfPromotion);
+        assert_eq!(ad.action, OnsiteAdAction::Click);
+    }
+
+    #[test]
+    fn from_event_name_variants() {
+        let ad = OnsiteAd::from_event_name("Publisher_Click").unwrap();
+        assert_eq!(ad.event_name(), "publisher.click");
+
+        let ad = OnsiteAd::from_event_name("self-promotion-impression").unwrap();
+        assert_eq!(ad.event_name(), "self_promotion.impression");
+
+        let ad = OnsiteAd::from_event_name("selfpromotion.click").unwrap();
+        assert_eq!(ad.event_name(), "self_promotion.click");
+    }
+
+    #[test]
+    fn from_event_name_unknown() {
+        assert!(OnsiteAd::from_event_name("").is_none());
+        assert!(OnsiteAd::from_event_name("publisher").is_none());
+        assert!(OnsiteAd::from_event_name("publisher.display").is_none());
+        assert!(OnsiteAd::from_event_name("click.action").is_none());
+        assert!(OnsiteAd::from_event_name("page.display").is_none());
+    }
+
+    #[test]
+    fn type_label() {
+        let ad = OnsiteAd::from_event_name("publisher.click").unwrap();
+        assert_eq!(ad.type_label(), "Publisher");
+        let ad = OnsiteAd::from_event_name("self_promotion.click").unwrap();
+        assert_eq!(ad.type_label(), "Self promotion");
+    }
+
+    #[test]
+    fn property_name_mapping() {
+        assert_eq!(OnsiteAd::property_name("creative"), "onsitead_creation");
+        assert_eq!(OnsiteAd::property_name("campaign"), "onsitead_campaign");
+        assert_eq!(OnsiteAd::property_name("format"), "onsitead_format");
+        assert_eq!(
+            OnsiteAd::property_name("placement"),
+            "onsitead_general_placement"
+        );
+        assert_eq!(OnsiteAd::property_name("other"), "other");
+        assert_eq!(
+            OnsiteAd::property_name("onsitead_variant"),
+            "onsitead_variant"
+        );
+    }
+}
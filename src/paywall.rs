@@ -0,0 +1,167 @@
+use crate::exports::edgee::components::data_collection::Dict;
+use crate::piano_payload::PianoData;
+
+// Paywall and subscription events
+//
+// track events sent by the paywall, and their Piano counterparts
+const EVENT_MAPPING: [(&str, &str); 2] = [
+    ("paywall_displayed", "paywall.display"),
+    ("subscription_started", "subscription.start"),
+];
+
+// subscription properties (from track or user properties), and their Piano counterparts
+const SUBSCRIPTION_PROPERTIES: [(&str, &str); 4] = [
+    ("subscription_id", "user_subscription_id"),
+    ("subscription_plan", "user_subscription_plan"),
+    ("subscription_status", "user_subscription_status"),
+    ("subscription_term", "user_subscription_term"),
+];
+
+// subscription statuses giving access to premium content
+const ACTIVE_SUBSCRIPTION_STATUSES: [&str; 2] = ["active", "trialing"];
+
+pub(crate) fn event_name(name: &str) -> Option<&'static str> {
+    EVENT_MAPPING
+        .iter()
+        .find(|(from, _)| *from == name)
+        .map(|(_, to)| *to)
+}
+
+// set the paywall property matching the given page or track property,
+// returns false if the property is not a paywall property
+pub(crate) fn apply_property(key: &str, value: &str, data: &mut PianoData) -> bool {
+    match key {
+        "has_access" => data.has_access = Some(value.to_string()),
+        "is_premium" | "content_is_premium" => match parse_bool(value) {
+            Some(premium) => data.content_is_premium = Some(premium),
+            None => return false,
+        },
+        _ => match subscription_property(key) {
+            Some(property) => {
+                data.additional_fields.insert(
+                    property.to_string(),
+                    serde_json::Value::String(value.to_string()),
+                );
+            }
+            None => return false,
+        },
+    }
+    true
+}
+
+// user_subscription_* properties from the user properties
+pub(crate) fn apply_user_properties(properties: &Dict, data: &mut PianoData) {
+    for (key, value) in properties.iter() {
+        if let Some(property) = subscription_property(key) {
+            data.additional_fields.insert(
+                property.to_string(),
+                serde_json::Value::String(value.clone()),
+            );
+        }
+    }
+}
+
+// has_access derived from the user properties, used when the page didn't provide it
+pub(crate) fn has_access_from_user(properties: &Dict) -> Option<String> {
+    if let Some((_, value)) = properties.iter().find(|(key, _)| key == "has_access") {
+        return Some(value.clone());
+    }
+    properties
+        .iter()
+        .find(|(key, _)| key == "subscription_status" || key == "user_subscription_status")
+        .map(|(_, status)| {
+            if ACTIVE_SUBSCRIPTION_STATUSES.contains(&status.to_lowercase().as_str()) {
+                "subscriber".to_string()
+            } else {
+                "registered".to_string()
+            }
+        })
+}
+
+fn subscription_property(key: &str) -> Option<&'static str> {
+    SUBSCRIPTION_PROPERTIES
+        .iter()
+        .find(|(from, to)| *from == key || *to == key)
+        .map(|(_, to)| *to)
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "1" | "yes" => Some(true),
+        "false" | "0" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_names() {
+        assert_eq!(event_name("paywall_displayed"), Some("paywall.display"));
+        assert_eq!(
+            event_name("subscription_started"),
+            Some("subscription.start")
+        );
+        assert_eq!(event_name("event-name"), None);
+    }
+
+    #[test]
+    fn apply_paywall_properties() {
+        let mut data = PianoData::default();
+        assert!(apply_property("has_access", "false", &mut data));
+        assert!(apply_property("is_premium", "1", &mut data));
+        assert!(apply_property("subscription_plan", "monthly", &mut data));
+        assert!(apply_property("user_subscription_term", "1m", &mut data));
+        assert!(!apply_property("is_premium", "maybe", &mut data));
+        assert!(!apply_property("prop1", "value1", &mut data));
+
+        assert_eq!(data.has_access, Some("false".to_string()));
+        assert_eq!(data.content_is_premium, Some(true));
+        assert_eq!(
+            data.additional_fields.get("user_subscription_plan"),
+            Some(&serde_json::Value::from("monthly"))
+        );
+        assert_eq!(
+            data.additional_fields.get("user_subscription_term"),
+            Some(&serde_json::Value::from("1m"))
+        );
+    }
+
+    #[test]
+    fn user_subscription_properties() {
+        let properties = vec![
+            ("subscription_status".to_string(), "active".to_string()),
+            ("prop1".to_string(), "value1".to_string()),
+        ];
+        let mut data = PianoData::default();
+        apply_user_properties(&properties, &mut data);
+        assert_eq!(
+            data.additional_fields.get("user_subscription_status"),
+            Some(&serde_json::Value::from("active"))
+        );
+        assert_eq!(data.additional_fields.len(), 1);
+    }
+
+    #[test]
+    fn has_access_derived_from_user() {
+        let properties = vec![("has_access".to_string(), "true".to_string())];
+        assert_eq!(has_access_from_user(&properties), Some("true".to_string()));
+
+        let properties = vec![("subscription_status".to_string(), "Active".to_string())];
+        assert_eq!(
+            has_access_from_user(&properties),
+            Some("subscriber".to_string())
+        );
+
+        let properties = vec![("subscription_status".to_string(), "canceled".to_string())];
+        assert_eq!(
+            has_access_from_user(&properties),
+            Some("registered".to_string())
+        );
+
+        let properties = vec![("prop1".to_string(), "value1".to_string())];
+        assert_eq!(has_access_from_user(&properties), None);
+    }
+}
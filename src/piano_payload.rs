@@ -1,10 +1,21 @@
-use anyhow::anyhow;
-use chrono::{TimeZone, Timelike, Utc};
+use chrono::NaiveTime;
 use serde::Serialize;
 use std::collections::HashMap;
-use std::str::FromStr;
 
-use crate::exports::edgee::components::data_collection::{Consent, Dict, Event};
+use crate::bot::{self, BotFiltering};
+use crate::campaign;
+use crate::content::ContentMapping;
+use crate::device;
+use crate::endpoint::{ContentType, Protocol, Scheme};
+use crate::error::PianoError;
+use crate::exports::edgee::components::data_collection::{Consent, Event};
+use crate::geo::GeoGranularity;
+use crate::headers;
+use crate::legacy_hit::SiteVariables;
+use crate::paywall;
+use crate::settings::PianoSettings;
+use crate::time::LocalTime;
+use crate::user_agent::UserAgent;
 
 #[derive(Serialize, Debug, Default)]
 pub(crate) struct PianoPayload {
@@ -15,33 +26,95 @@ pub(crate) struct PianoPayload {
     #[serde(skip)]
     pub id_client: String,
     #[serde(skip)]
+    pub collection_scheme: Scheme,
+    #[serde(skip)]
+    pub collection_path: String,
+    #[serde(skip)]
+    pub protocol: Protocol,
+    #[serde(skip)]
+    pub content_type: ContentType,
+    #[serde(skip)]
+    pub as2_site_variables: SiteVariables,
+    #[serde(skip)]
     pub collect_utm_as_properties: bool,
+    #[serde(skip)]
+    pub content_mapping: ContentMapping,
+    #[serde(skip)]
+    pub error_page_url_patterns: Vec<String>,
+    #[serde(skip)]
+    pub geo_granularity: GeoGranularity,
+    #[serde(skip)]
+    pub headers: Vec<(String, String)>,
+    #[serde(skip)]
+    pub forward_client_headers: bool,
+    #[serde(skip)]
+    pub is_bot: bool,
     pub(crate) events: Vec<PianoEvent>,
 }
 
 impl PianoPayload {
-    pub(crate) fn new(edgee_event: &Event, settings: Dict) -> anyhow::Result<Self> {
-        let cred: HashMap<String, String> = settings
-            .iter()
-            .map(|(key, value)| (key.to_string(), value.to_string()))
-            .collect();
-
-        let site_id = match cred.get("piano_site_id") {
+    pub(crate) fn new(
+        event_name: &str,
+        edgee_event: &Event,
+        settings: &PianoSettings,
+    ) -> Result<Self, PianoError> {
+        // site id and collection domain can be configured per hostname of the page url
+        let hostname_site = settings.hostname_sites.find(&edgee_event.context.page.url);
+
+        let mut site_id = match hostname_site
+            .as_ref()
+            .map(|site| &site.site_id)
+            .or(settings.site_id.as_ref())
+        {
             Some(key) => key,
-            None => return Err(anyhow!("Missing piano site id")),
+            None => return Err(PianoError::Config("Missing piano site id".to_string())),
         }
         .to_string();
 
-        let collection_domain = match cred.get("piano_collection_domain") {
+        // events can be sent to another site depending on the routing rules
+        if let Some(routed_site_id) = settings.site_routing.site_id(event_name, edgee_event) {
+            site_id = routed_site_id.to_string();
+        }
+
+        let collection_domain = match hostname_site
+            .as_ref()
+            .and_then(|site| site.collection_domain.as_ref())
+            .or(settings.collection_domain.as_ref())
+        {
             Some(key) => key,
-            None => return Err(anyhow!("Missing piano collection domain")),
+            None => {
+                return Err(PianoError::Config(
+                    "Missing piano collection domain".to_string(),
+                ))
+            }
         }
         .to_string();
 
-        let collect_utm_as_properties = match cred.get("piano_collect_utm_as_properties") {
-            Some(value) => value == "true",
-            None => false,
-        };
+        // client headers sent explicitly (the client IP being truncated without consent) are no longer
+        // forwarded by Edgee, static custom headers are added to them
+        let mut headers = vec![];
+        for header in &settings.forwarded_headers {
+            if let Some(value) = header.value(edgee_event) {
+                headers.push((header.name().to_string(), value));
+            }
+        }
+        for (name, value) in &settings.custom_headers {
+            headers::set(&mut headers, name, value);
+        }
+        let forward_client_headers =
+            settings.forward_client_headers && settings.forwarded_headers.is_empty();
+
+        // bots are detected from the user agent, then tagged or dropped
+        let is_bot = settings.bot_filtering != BotFiltering::Off
+            && bot::is_bot(
+                &edgee_event.context.client.user_agent,
+                &settings.bot_patterns,
+            );
+        if is_bot && settings.bot_filtering == BotFiltering::Drop {
+            return Err(PianoError::Filtered(
+                "Event from a bot, dropped".to_string(),
+            ));
+        }
 
         let id_client = edgee_event.context.user.edgee_id.to_string();
 
@@ -49,7 +122,21 @@ impl PianoPayload {
             site_id,
             collection_domain,
             id_client,
-            collect_utm_as_properties,
+            collection_scheme: settings.collection_scheme,
+            collection_path: settings
+                .collection_path
+                .clone()
+                .unwrap_or_else(|| settings.protocol.default_path().to_string()),
+            protocol: settings.protocol,
+            content_type: settings.content_type,
+            as2_site_variables: settings.as2_site_variables.clone(),
+            collect_utm_as_properties: settings.collect_utm_as_properties,
+            content_mapping: settings.content_mapping.clone(),
+            error_page_url_patterns: settings.error_page_url_patterns.clone(),
+            geo_granularity: settings.geo_granularity,
+            headers,
+            forward_client_headers,
+            is_bot,
             events: vec![],
         })
     }
@@ -64,8 +151,8 @@ impl PianoEvent {
     pub(crate) fn new(
         name: &str,
         edgee_event: &Event,
-        collect_utm_as_properties: bool,
-    ) -> anyhow::Result<Self> {
+        payload: &PianoPayload,
+    ) -> Result<Self, PianoError> {
         let mut event = PianoEvent::default();
 
         // Standard properties
@@ -77,12 +164,17 @@ impl PianoEvent {
             ..PianoData::default()
         };
 
+        // bots are only detected when bot filtering is enabled
+        if payload.is_bot {
+            data.is_bot = Some(true);
+        }
+
         // pageview_id (even if the event is not a pageview, we set the pageview_id to the event uuid)
         data.pageview_id = Some(edgee_event.uuid.clone());
 
         // event_url_full
         if !edgee_event.context.page.url.is_empty() {
-            if edgee_event.consent.is_some() && edgee_event.consent.unwrap() == Consent::Granted {
+            if edgee_event.consent == Some(Consent::Granted) {
                 data.event_url_full = Some(edgee_event.context.page.url.clone());
             } else {
                 let url_without_qs = edgee_event
@@ -104,31 +196,31 @@ impl PianoEvent {
 
         // Locale
         let locale = edgee_event.context.client.locale.clone();
-        if locale.contains("-") {
-            let parts: Vec<&str> = locale.split("-").collect();
-            data.browser_language = parts[0].to_string();
-            data.browser_language_local = parts[1].to_uppercase();
+        if let Some((language, local)) = locale.split_once('-') {
+            data.browser_language = language.to_string();
+            data.browser_language_local = local.to_uppercase();
         } else {
             data.browser_language = locale.clone();
             data.browser_language_local = locale.clone().to_uppercase();
         }
 
-        // device hour
-        let timestamp = Utc.timestamp_opt(edgee_event.timestamp, 0);
-        if !edgee_event.context.client.timezone.is_empty() {
-            // this part uses the chrono_tz crate to convert the timestamp to the client timezone
-            // unfortunately, the crate increase the weight of the binary by 0.9MB... this is huge
-            let tz = chrono_tz::Tz::from_str(edgee_event.context.client.timezone.as_str());
-            if tz.is_ok() {
-                let tz = tz?;
-                let dt = timestamp.unwrap().with_timezone(&tz);
-                data.device_hour = dt.hour() as i64;
-            }
-        } else {
-            data.device_hour = timestamp.unwrap().hour() as i64;
-        }
+        // device time
+        let local_time = LocalTime::new(
+            edgee_event.timestamp,
+            edgee_event.context.client.timezone.as_str(),
+        )
+        .ok_or_else(|| {
+            PianoError::Validation(format!(
+                "Invalid event timestamp: {}",
+                edgee_event.timestamp
+            ))
+        })?;
+        data.device_hour = local_time.hour as i64;
+        data.device_local_hour = local_time.hour as i64;
+        data.device_local_date = Some(local_time.date);
+        data.device_local_weekday = Some(local_time.weekday);
+        data.device_utc_offset = Some(local_time.utc_offset);
         data.device_timestamp_utc = edgee_event.timestamp_millis;
-        data.device_local_hour = edgee_event.timestamp_millis;
 
         // User Agent
         let ua_version = edgee_event.context.client.user_agent_version_list.clone();
@@ -147,7 +239,7 @@ impl PianoEvent {
                 data.ch_ua_full_version = data
                     .ch_ua_full_version_list
                     .iter()
-                    .find(|ua| !ua.brand.starts_with("Not"))
+                    .find(|ua| !is_grease_brand(&ua.brand))
                     .map(|ua| ua.version.clone())
                     .unwrap_or_else(|| data.ch_ua_full_version_list[0].version.clone());
             }
@@ -165,26 +257,26 @@ impl PianoEvent {
         data.ch_ua_platform = edgee_event.context.client.os_name.clone();
         data.ch_ua_platform_version = edgee_event.context.client.os_version.clone();
 
-        // screen size
+        // no client hints (Safari, Firefox...), parse the user agent string instead
+        if ua_version.is_empty() && !edgee_event.context.client.user_agent.is_empty() {
+            UserAgent::parse(&edgee_event.context.client.user_agent).apply(&mut data);
+        }
+
+        // screen size (device_display_* is the viewport, set from the page or track properties)
         if edgee_event.context.client.screen_width.is_positive() {
-            data.device_display_width = edgee_event.context.client.screen_width as i64;
             data.device_screen_width = edgee_event.context.client.screen_width as i64;
         }
         if edgee_event.context.client.screen_height.is_positive() {
-            data.device_display_height = edgee_event.context.client.screen_height as i64;
             data.device_screen_height = edgee_event.context.client.screen_height as i64;
         }
+        data.device_pixel_ratio = device::pixel_ratio(edgee_event.context.client.screen_density);
 
         // cookie_creation_date
         // get the first seen date from the session and convert it to a datetime string
         let first_seen_i64 = edgee_event.context.session.first_seen;
         let first_seen_opt = chrono::DateTime::from_timestamp(first_seen_i64, 0);
         if let Some(first_seen) = first_seen_opt {
-            let midnight = first_seen
-                .date_naive()
-                .and_hms_opt(0, 0, 0)
-                .unwrap()
-                .and_utc();
+            let midnight = first_seen.date_naive().and_time(NaiveTime::MIN).and_utc();
             data.cookie_creation_date = Some(midnight.to_rfc3339());
         }
 
@@ -203,7 +295,7 @@ impl PianoEvent {
             }
         }
 
-        if edgee_event.consent.is_some() && edgee_event.consent.unwrap() == Consent::Granted {
+        if edgee_event.consent == Some(Consent::Granted) {
             data.visitor_privacy_consent = true;
             data.visitor_privacy_mode = "optin".to_string();
         } else {
@@ -216,7 +308,7 @@ impl PianoEvent {
         // We first use the standard campaign parameters coming from UTM, then we override them with the at_* parameters
         // get all at_* properties from edgee_event.context.page.search and add them to data.src_* properties
         // https://developers.atinternet-solutions.com/piano-analytics/data-collection/how-to-send-events/marketing-campaigns
-        if edgee_event.consent.is_some() && edgee_event.consent.unwrap() == Consent::Granted {
+        if edgee_event.consent == Some(Consent::Granted) {
             if !edgee_event.context.campaign.medium.is_empty() {
                 data.src_medium = Some(edgee_event.context.campaign.medium.clone());
             }
@@ -242,75 +334,37 @@ impl PianoEvent {
             }
             // missing: src_source_platform and src_id
             if !edgee_event.context.page.search.is_empty() {
-                // analyze search string
-                if let Ok(qs_map) = serde_qs::from_str::<HashMap<String, String>>(
+                campaign::apply_search_parameters(
                     edgee_event.context.page.search.as_str(),
-                ) {
-                    for (key, value) in qs_map.iter() {
-                        // key could start with ?
-                        let key = key.trim_start_matches("?");
-
-                        if key.starts_with("utm_") {
-                            if collect_utm_as_properties {
-                                data.additional_fields
-                                    .insert(key.to_string(), parse_value(value));
-                            }
-                            match key {
-                                "utm_campaign" => data.src_campaign = Some(value.clone()),
-                                "utm_content" => data.src_content = Some(value.clone()),
-                                "utm_medium" => data.src_medium = Some(value.clone()),
-                                "utm_creative_format" => {
-                                    data.src_creative_format = Some(value.clone())
-                                }
-                                "utm_id" => data.src_id = Some(value.clone()),
-                                "utm_marketing_tactic" => {
-                                    data.src_marketing_tactic = Some(value.clone())
-                                }
-                                "utm_source" => data.src_source = Some(value.clone()),
-                                "utm_source_platform" => {
-                                    data.src_source_platform = Some(value.clone())
-                                }
-                                "utm_term" => data.src_term = Some(value.clone()),
-                                _ => {
-                                    if !collect_utm_as_properties {
-                                        // replace utm_ with src_
-                                        data.additional_fields.insert(
-                                            key.replace("utm_", "src_"),
-                                            parse_value(value),
-                                        );
-                                        data.additional_fields
-                                            .insert(key.to_string(), parse_value(value));
-                                    }
-                                }
-                            }
-                        }
-                        if key.starts_with("at_") {
-                            match key {
-                                "at_campaign" => data.src_campaign = Some(value.clone()),
-                                "at_content" => data.src_content = Some(value.clone()),
-                                "at_medium" => data.src_medium = Some(value.clone()),
-                                "at_creative_format" => {
-                                    data.src_creative_format = Some(value.clone())
-                                }
-                                "at_id" => data.src_id = Some(value.clone()),
-                                "at_marketing_tactic" => {
-                                    data.src_marketing_tactic = Some(value.clone())
-                                }
-                                "at_source" => data.src_source = Some(value.clone()),
-                                "at_source_platform" => {
-                                    data.src_source_platform = Some(value.clone())
-                                }
-                                "at_term" => data.src_term = Some(value.clone()),
-                                _ => {
-                                    // replace at_ with src_
-                                    data.additional_fields
-                                        .insert(key.replace("at_", "src_"), parse_value(value));
-                                }
-                            }
-                        }
-                    }
-                }
+                    payload.collect_utm_as_properties,
+                    &mut data,
+                );
+            }
+        }
+
+        // Visit
+        //
+        // visit identifiers are only sent if consent is granted
+        let session = &edgee_event.context.session;
+        if edgee_event.consent == Some(Consent::Granted) {
+            if !session.session_id.is_empty() {
+                data.visit_id = Some(session.session_id.clone());
             }
+            if !session.previous_session_id.is_empty() {
+                data.visit_previous_id = Some(session.previous_session_id.clone());
+            }
+        }
+        if session.session_count > 0 {
+            data.visit_count = Some(session.session_count);
+            data.visitor_new = Some(session.session_count == 1);
+        }
+        // time since last visit, in seconds, on the first event of a returning visitor's visit
+        if session.session_start
+            && session.session_count > 1
+            && session.last_seen > 0
+            && edgee_event.timestamp >= session.last_seen
+        {
+            data.visit_time_since_last = Some(edgee_event.timestamp - session.last_seen);
         }
 
         // User
@@ -329,12 +383,15 @@ impl PianoEvent {
                     data.user_category = Some(value.clone());
                 }
             }
+            paywall::apply_user_properties(&edgee_event.context.user.properties, &mut data);
         }
 
         // Geo
-        if !edgee_event.context.client.country_code.is_empty() {
-            data.geo_country_code_alpha2 = Some(edgee_event.context.client.country_code.clone());
-        }
+        payload.geo_granularity.apply(
+            &edgee_event.context.client,
+            edgee_event.consent == Some(Consent::Granted),
+            &mut data,
+        );
 
         event.name = name.to_string();
         event.data = data;
@@ -347,8 +404,9 @@ pub fn parse_value(value: &str) -> serde_json::Value {
         serde_json::Value::from(true)
     } else if value == "false" {
         serde_json::Value::from(false)
-    } else if value.parse::<f64>().is_ok() {
-        serde_json::Value::Number(value.parse().unwrap())
+    } else if let Ok(number) = value.parse::<serde_json::Number>() {
+        // only JSON numbers, strings like inf, NaN or +1 are kept as strings
+        serde_json::Value::Number(number)
     } else {
         serde_json::Value::String(value.to_string())
     }
@@ -377,20 +435,42 @@ pub(crate) struct PianoData {
     #[serde(skip_serializing_if = "String::is_empty")]
     pub ch_ua_platform_version: String,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_is_premium: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub content_keywords: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_publication_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_section: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub content_title: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub cookie_creation_date: Option<String>,
 
-    pub device_display_width: i64,
-    pub device_display_height: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_display_width: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_display_height: Option<i64>,
     pub device_hour: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_local_date: Option<String>,
     pub device_local_hour: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_local_weekday: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_pixel_ratio: Option<f64>,
     pub device_screen_width: i64,
     pub device_screen_height: i64,
     pub device_timestamp_utc: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_utc_offset: Option<i32>,
 
     pub event_collection_platform: String,
     pub event_collection_version: String,
@@ -400,6 +480,10 @@ pub(crate) struct PianoData {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub has_access: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_bot: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_error_page: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub page_title_html: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub page_name: Option<String>,
@@ -411,6 +495,16 @@ pub(crate) struct PianoData {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub previous_url: Option<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visit_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visit_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visit_previous_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visit_time_since_last: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visitor_new: Option<bool>,
     pub visitor_privacy_consent: bool,
     pub visitor_privacy_mode: String,
 
@@ -438,8 +532,16 @@ pub(crate) struct PianoData {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user_category: Option<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geo_city: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geo_continent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geo_country: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub geo_country_code_alpha2: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geo_region: Option<String>,
 
     #[serde(flatten)]
     pub additional_fields: HashMap<String, serde_json::Value>,
@@ -451,55 +553,135 @@ pub(crate) struct ChUa {
     pub version: String,
 }
 
+// Brand list of the client hints, in Edgee's form (Brand;version|Brand;version) or in the Sec-CH-UA
+// structured header form ("Brand";v="version", "Brand";v="version")
+// Versions are truncated to their major version (leading digits) unless full is true.
 fn string_to_ch_ua(string: &str, full: bool) -> Vec<ChUa> {
-    let mut ch_ua_list = vec![];
-    if string.contains("|") {
-        let parts: Vec<&str> = string.split("|").collect();
-        for part in parts {
-            if part.contains(";") {
-                let parts: Vec<&str> = part.split(";").collect();
-                if parts.len() < 2 {
-                    continue;
-                }
-                // brand is the first parts, less the last part
-                let brand = parts[0..parts.len() - 1].join(";");
-                // version is the last part
-                let mut version = parts[parts.len() - 1];
-                if !full && version.contains(".") {
-                    let parts: Vec<&str> = version.split(".").collect();
-                    version = parts[0];
+    let string = string.trim();
+    let entries = if string.starts_with('"') {
+        parse_sec_ch_ua(string)
+    } else {
+        string
+            .split('|')
+            .filter_map(|entry| {
+                // brands may contain ; (e.g. Not;A=Brand), the version is after the last one
+                let (brand, version) = entry.rsplit_once(';')?;
+                Some((brand.trim().to_string(), version.trim().to_string()))
+            })
+            .collect()
+    };
+
+    entries
+        .into_iter()
+        .filter(|(brand, version)| !brand.is_empty() && !version.is_empty())
+        .map(|(brand, version)| ChUa {
+            brand,
+            version: if full {
+                version
+            } else {
+                major_version(&version).to_string()
+            },
+        })
+        .collect()
+}
+
+// leading digits of the version, the version itself if it doesn't start with a digit
+fn major_version(version: &str) -> &str {
+    let end = version
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(version.len());
+    if end == 0 {
+        version
+    } else {
+        &version[..end]
+    }
+}
+
+// GREASE brands (e.g. "Not;A=Brand", "Not A(Brand", "Not/A)Brand") are random brands added by Chromium
+// to the brand list, they should be ignored when looking for the browser version
+fn is_grease_brand(brand: &str) -> bool {
+    brand
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .eq("NotABrand".chars())
+}
+
+// (brand, version) of a Sec-CH-UA structured header list, items with an invalid syntax are skipped
+fn parse_sec_ch_ua(string: &str) -> Vec<(String, String)> {
+    let mut entries = vec![];
+    let mut chars = string.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let brand = parse_sf_item(&mut chars);
+        let mut version = None;
+        // parameters
+        loop {
+            while chars.next_if(|c| *c == ' ').is_some() {}
+            if chars.next_if_eq(&';').is_none() {
+                break;
+            }
+            while chars.next_if(|c| *c == ' ').is_some() {}
+            let key: String =
+                std::iter::from_fn(|| chars.next_if(|c| !matches!(c, '=' | ';' | ','))).collect();
+            if chars.next_if_eq(&'=').is_some() {
+                let value = parse_sf_item(&mut chars);
+                if key.trim() == "v" {
+                    version = value;
                 }
-                ch_ua_list.push(ChUa {
-                    brand: brand.to_string(),
-                    version: version.to_string(),
-                });
             }
         }
-    } else if string.contains(";") {
-        let parts: Vec<&str> = string.split(";").collect();
-        if parts.len() < 2 {
-            return ch_ua_list;
-        }
-        // brand is the first parts, less the last part
-        let brand = parts[0..parts.len() - 1].join(";");
-        // version is the last part
-        let mut version = parts[parts.len() - 1];
-        if !full && version.contains(".") {
-            let parts: Vec<&str> = version.split(".").collect();
-            version = parts[0];
-        }
-        ch_ua_list.push(ChUa {
-            brand: brand.to_string(),
-            version: version.to_string(),
-        });
+        // skip anything left until the next item
+        let garbage = std::iter::from_fn(|| chars.next_if(|c| *c != ',')).count();
+        chars.next();
+
+        if let (Some(brand), Some(version), 0) = (brand, version, garbage) {
+            entries.push((brand, version));
+        }
     }
+    entries
+}
 
-    ch_ua_list
+// quoted string (with \ escapes) or token, None if the string is not terminated
+fn parse_sf_item(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<String> {
+    if chars.next_if_eq(&'"').is_some() {
+        let mut value = String::new();
+        loop {
+            match chars.next()? {
+                '"' => return Some(value),
+                '\\' => value.push(chars.next()?),
+                c => value.push(c),
+            }
+        }
+    } else {
+        Some(std::iter::from_fn(|| chars.next_if(|c| !matches!(c, ';' | ',' | ' '))).collect())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn parse_values() {
+        assert_eq!(parse_value("true"), serde_json::json!(true));
+        assert_eq!(parse_value("false"), serde_json::json!(false));
+        assert_eq!(parse_value("42"), serde_json::json!(42));
+        assert_eq!(parse_value("-1.5"), serde_json::json!(-1.5));
+        assert_eq!(parse_value("text"), serde_json::json!("text"));
+    }
+
+    #[test]
+    fn parse_non_json_numbers_as_strings() {
+        for value in ["inf", "-inf", "infinity", "NaN", "+1", ".5", "1.", "1e400"] {
+            assert_eq!(parse_value(value), serde_json::json!(value), "{}", value);
+        }
+    }
 
     #[test]
     fn string_to_chua_vec_single_entry() {
@@ -576,7 +758,7 @@ mod tests {
         let result = string_to_ch_ua(input, false);
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].brand, "Brand1");
-        assert_eq!(result[0].version, "1,0");
+        assert_eq!(result[0].version, "1");
     }
 
     #[test]
@@ -616,4 +798,107 @@ mod tests {
         assert_eq!(result[1].brand, "Brand2");
         assert_eq!(result[1].version, "2.0.0");
     }
+
+    #[test]
+    fn string_to_chua_vec_sec_ch_ua() {
+        let input = r#""Chromium";v="128", "Not;A=Brand";v="24", "Google Chrome";v="128""#;
+        let result = string_to_ch_ua(input, false);
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0].brand, "Chromium");
+        assert_eq!(result[0].version, "128");
+        assert_eq!(result[1].brand, "Not;A=Brand");
+        assert_eq!(result[1].version, "24");
+        assert_eq!(result[2].brand, "Google Chrome");
+        assert_eq!(result[2].version, "128");
+    }
+
+    #[test]
+    fn string_to_chua_vec_sec_ch_ua_full() {
+        let input = r#""Not A(Brand";v="8.0.0.0", "Chromium";v="120.0.6099.109""#;
+        let result = string_to_ch_ua(input, true);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].brand, "Not A(Brand");
+        assert_eq!(result[0].version, "8.0.0.0");
+        assert_eq!(result[1].version, "120.0.6099.109");
+
+        let result = string_to_ch_ua(input, false);
+        assert_eq!(result[1].version, "120");
+    }
+
+    #[test]
+    fn string_to_chua_vec_sec_ch_ua_escapes_and_invalid_items() {
+        let input = r#""Brand \"1\", Inc";v="1.2", "NoVersion", "Unterminated;v="3", x"#;
+        let result = string_to_ch_ua(input, true);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].brand, r#"Brand "1", Inc"#);
+        assert_eq!(result[0].version, "1.2");
+
+        let input = r#""Brand";v="1"garbage, "Other";v=2;x=y"#;
+        let result = string_to_ch_ua(input, true);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].brand, "Other");
+        assert_eq!(result[0].version, "2");
+    }
+
+    #[test]
+    fn grease_brands() {
+        assert!(is_grease_brand("Not;A=Brand"));
+        assert!(is_grease_brand("Not A(Brand"));
+        assert!(is_grease_brand("Not/A)Brand"));
+        assert!(is_grease_brand("Not_A Brand"));
+        assert!(!is_grease_brand("Google Chrome"));
+        assert!(!is_grease_brand("Notion"));
+    }
+
+    fn brand_list() -> impl Strategy<Value = Vec<(String, String)>> {
+        prop::collection::vec(
+            (
+                "[A-Za-z][A-Za-z0-9 ();=/._-]{0,15}[A-Za-z]",
+                "[0-9]{1,3}(\\.[0-9]{1,4}){0,3}",
+            ),
+            0..5,
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn string_to_chua_vec_never_panics(input in "\\PC*", full: bool) {
+            string_to_ch_ua(&input, full);
+        }
+
+        #[test]
+        fn string_to_chua_vec_edgee_round_trip(brands in brand_list()) {
+            let input = brands
+                .iter()
+                .map(|(brand, version)| format!("{};{}", brand, version))
+                .collect::<Vec<_>>()
+                .join("|");
+            let full = string_to_ch_ua(&input, true);
+            let truncated = string_to_ch_ua(&input, false);
+            prop_assert_eq!(full.len(), brands.len());
+            prop_assert_eq!(truncated.len(), brands.len());
+            for (i, (brand, version)) in brands.iter().enumerate() {
+                prop_assert_eq!(&full[i].brand, brand.trim());
+                prop_assert_eq!(&full[i].version, version);
+                prop_assert_eq!(&truncated[i].version, version.split('.').next().unwrap());
+            }
+        }
+
+        #[test]
+        fn string_to_chua_vec_sec_ch_ua_round_trip(brands in brand_list()) {
+            let input = brands
+                .iter()
+                .map(|(brand, version)| format!("{:?};v=\"{}\"", brand, version))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let full = string_to_ch_ua(&input, true);
+            let truncated = string_to_ch_ua(&input, false);
+            prop_assert_eq!(full.len(), brands.len());
+            for (i, (brand, version)) in brands.iter().enumerate() {
+                prop_assert_eq!(&full[i].brand, brand);
+                prop_assert_eq!(&full[i].version, version);
+                prop_assert_eq!(&truncated[i].version, version.split('.').next().unwrap());
+            }
+        }
+    }
 }
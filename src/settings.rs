@@ -0,0 +1,474 @@
+use anyhow::{anyhow, Context};
+use std::collections::HashMap;
+
+use crate::bot::BotFiltering;
+use crate::content::ContentMapping;
+use crate::endpoint::{self, ContentType, Protocol, Scheme};
+use crate::exports::edgee::components::data_collection::Dict;
+use crate::geo::GeoGranularity;
+use crate::headers::{self, ForwardedHeader};
+use crate::legacy_hit::SiteVariables;
+use crate::routing::{HostnameSites, SiteRouting};
+
+// Component settings
+//
+// Parsed and validated once per event, before building the payload, so a misconfiguration is reported
+// with the name of the faulty setting. Settings not prefixed with piano_ (e.g. edgee_*) are ignored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct PianoSettings {
+    pub site_id: Option<String>,
+    pub collection_domain: Option<String>,
+    pub hostname_sites: HostnameSites,
+    pub site_routing: SiteRouting,
+    pub collect_utm_as_properties: bool,
+    pub content_mapping: ContentMapping,
+    pub error_page_url_patterns: Vec<String>,
+    pub geo_granularity: GeoGranularity,
+    pub send_client_ip: bool,
+    pub forward_client_headers: bool,
+    pub forwarded_headers: Vec<ForwardedHeader>,
+    pub custom_headers: Vec<(String, String)>,
+    pub bot_filtering: BotFiltering,
+    pub bot_patterns: Vec<String>,
+    pub collection_scheme: Scheme,
+    pub collection_path: Option<String>,
+    pub protocol: Protocol,
+    pub content_type: ContentType,
+    pub as2_site_variables: SiteVariables,
+}
+
+impl PianoSettings {
+    pub(crate) fn parse(settings: &Dict) -> anyhow::Result<Self> {
+        let values = Values(
+            settings
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.trim()))
+                .collect(),
+        );
+
+        let site_id = values
+            .get("piano_site_id")
+            .map(|value| {
+                validate_site_id(value)?;
+                Ok::<_, anyhow::Error>(value.to_string())
+            })
+            .transpose()
+            .context("Invalid piano_site_id setting")?;
+        let collection_domain = values
+            .get("piano_collection_domain")
+            .map(normalize_collection_domain)
+            .transpose()
+            .context("Invalid piano_collection_domain setting")?;
+        let hostname_sites = values
+            .get("piano_hostname_sites")
+            .map(HostnameSites::parse)
+            .transpose()
+            .context("Invalid piano_hostname_sites setting")?
+            .unwrap_or_default();
+
+        // without hostname sites, the default site is always used
+        if hostname_sites == HostnameSites::default() {
+            if site_id.is_none() {
+                return Err(anyhow!("Missing piano site id"));
+            }
+            if collection_domain.is_none() {
+                return Err(anyhow!("Missing piano collection domain"));
+            }
+        }
+
+        // piano_send_client_ip is a shorthand for forwarding x-forwarded-for and user-agent
+        let send_client_ip = values.bool("piano_send_client_ip", false)?;
+        let mut forwarded_headers = vec![];
+        if send_client_ip {
+            forwarded_headers = vec![ForwardedHeader::XForwardedFor, ForwardedHeader::UserAgent];
+        }
+        if let Some(value) = values.get("piano_forwarded_headers") {
+            for header in ForwardedHeader::parse_list(value)
+                .context("Invalid piano_forwarded_headers setting")?
+            {
+                if !forwarded_headers.contains(&header) {
+                    forwarded_headers.push(header);
+                }
+            }
+        }
+
+        Ok(Self {
+            site_id,
+            collection_domain,
+            hostname_sites,
+            site_routing: values
+                .get("piano_site_routing")
+                .map(SiteRouting::parse)
+                .transpose()
+                .context("Invalid piano_site_routing setting")?
+                .unwrap_or_default(),
+            collect_utm_as_properties: values.bool("piano_collect_utm_as_properties", false)?,
+            // an empty content mapping disables the default one
+            content_mapping: values
+                .0
+                .get("piano_content_mapping")
+                .map(|value| ContentMapping::parse(value))
+                .transpose()
+                .context("Invalid piano_content_mapping setting")?
+                .unwrap_or_default(),
+            error_page_url_patterns: values.list("piano_error_page_url_patterns"),
+            geo_granularity: values
+                .get("piano_geo_granularity")
+                .map(GeoGranularity::parse)
+                .transpose()
+                .context("Invalid piano_geo_granularity setting")?
+                .unwrap_or_default(),
+            send_client_ip,
+            forward_client_headers: values.bool("piano_forward_client_headers", true)?,
+            forwarded_headers,
+            custom_headers: values
+                .get("piano_custom_headers")
+                .map(headers::parse_custom_headers)
+                .transpose()
+                .context("Invalid piano_custom_headers setting")?
+                .unwrap_or_default(),
+            bot_filtering: values
+                .get("piano_bot_filtering")
+                .map(BotFiltering::parse)
+                .transpose()
+                .context("Invalid piano_bot_filtering setting")?
+                .unwrap_or_default(),
+            bot_patterns: values.list("piano_bot_patterns"),
+            collection_scheme: values
+                .get("piano_collection_scheme")
+                .map(Scheme::parse)
+                .transpose()
+                .context("Invalid piano_collection_scheme setting")?
+                .unwrap_or_default(),
+            collection_path: values
+                .get("piano_collection_path")
+                .map(endpoint::normalize_path)
+                .transpose()
+                .context("Invalid piano_collection_path setting")?,
+            protocol: values
+                .get("piano_protocol")
+                .map(Protocol::parse)
+                .transpose()
+                .context("Invalid piano_protocol setting")?
+                .unwrap_or_default(),
+            content_type: values
+                .get("piano_content_type")
+                .map(ContentType::parse)
+                .transpose()
+                .context("Invalid piano_content_type setting")?
+                .unwrap_or_default(),
+            as2_site_variables: values
+                .get("piano_as2_site_variables")
+                .map(SiteVariables::parse)
+                .transpose()
+                .context("Invalid piano_as2_site_variables setting")?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+struct Values<'a>(HashMap<&'a str, &'a str>);
+
+impl Values<'_> {
+    // empty values are considered as not set
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).copied().filter(|value| !value.is_empty())
+    }
+
+    // booleans must be true or false
+    fn bool(&self, key: &str, default: bool) -> anyhow::Result<bool> {
+        match self.get(key) {
+            None => Ok(default),
+            Some(value) => match value.to_lowercase().as_str() {
+                "true" => Ok(true),
+                "false" => Ok(false),
+                _ => Err(anyhow!(
+                    "Invalid {} setting: expected true or false, got {}",
+                    key,
+                    value
+                )),
+            },
+        }
+    }
+
+    // comma-separated values, empty entries are ignored
+    fn list(&self, key: &str) -> Vec<String> {
+        match self.get(key) {
+            Some(value) => value
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect(),
+            None => vec![],
+        }
+    }
+}
+
+// Piano site ids are numeric
+pub(crate) fn validate_site_id(site_id: &str) -> anyhow::Result<()> {
+    if site_id.is_empty() || !site_id.chars().all(|c| c.is_ascii_digit()) {
+        return Err(anyhow!("Site id must be numeric, got {}", site_id));
+    }
+    Ok(())
+}
+
+// hostname (and optional port) of a collection domain, without the http(s) scheme nor trailing slashes,
+// e.g. xxxxxxx.pa-cd.com for https://xxxxxxx.pa-cd.com/
+pub(crate) fn normalize_collection_domain(value: &str) -> anyhow::Result<String> {
+    let value = value.trim();
+    let hostname = match value.split_once("://") {
+        Some((scheme, rest))
+            if scheme.eq_ignore_ascii_case("https") || scheme.eq_ignore_ascii_case("http") =>
+        {
+            rest
+        }
+        Some(_) => "",
+        None => value,
+    }
+    .trim_end_matches('/');
+
+    // a port is allowed for local stand-ins of the collection endpoint
+    let (host, port) = match hostname.rsplit_once(':') {
+        Some((host, port)) => (host, Some(port)),
+        None => (hostname, None),
+    };
+    let valid_port = port.is_none_or(|port| port.parse::<u16>().is_ok_and(|port| port > 0));
+    if !is_hostname(host) || !valid_port {
+        return Err(anyhow!(
+            "Collection domain must be a hostname, got {}",
+            value
+        ));
+    }
+    Ok(hostname.to_string())
+}
+
+// hostname without scheme, port nor path
+fn is_hostname(hostname: &str) -> bool {
+    !hostname.is_empty()
+        && hostname.len() <= 253
+        && hostname.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dict(settings: &[(&str, &str)]) -> Dict {
+        settings
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn error(settings: &[(&str, &str)]) -> String {
+        format!("{:#}", PianoSettings::parse(&dict(settings)).unwrap_err())
+    }
+
+    #[test]
+    fn parse_minimal_settings() {
+        let settings = PianoSettings::parse(&dict(&[
+            ("piano_site_id", "123456"),
+            ("piano_collection_domain", "xxxxxxx.pa-cd.com"),
+            ("edgee_anonymization", "true"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            settings,
+            PianoSettings {
+                site_id: Some("123456".to_string()),
+                collection_domain: Some("xxxxxxx.pa-cd.com".to_string()),
+                forward_client_headers: true,
+                ..PianoSettings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_all_settings() {
+        let settings = PianoSettings::parse(&dict(&[
+            ("piano_site_id", " 123456 "),
+            ("piano_collection_domain", "xxxxxxx.pa-cd.com"),
+            ("piano_hostname_sites", "example.fr=234567"),
+            ("piano_site_routing", "country:FR=345678"),
+            ("piano_collect_utm_as_properties", "TRUE"),
+            ("piano_content_mapping", "content_id=id"),
+            ("piano_error_page_url_patterns", "/404, ,/not-found"),
+            ("piano_geo_granularity", "country"),
+            ("piano_send_client_ip", "false"),
+            ("piano_bot_filtering", "tag"),
+            ("piano_bot_patterns", "monitor"),
+        ]))
+        .unwrap();
+        assert_eq!(settings.site_id, Some("123456".to_string()));
+        assert!(settings.collect_utm_as_properties);
+        assert!(!settings.send_client_ip);
+        assert_eq!(settings.error_page_url_patterns, vec!["/404", "/not-found"]);
+        assert_eq!(settings.geo_granularity, GeoGranularity::Country);
+        assert_eq!(settings.bot_filtering, BotFiltering::Tag);
+        assert_eq!(settings.bot_patterns, vec!["monitor"]);
+        assert!(settings.forward_client_headers);
+        assert!(settings.forwarded_headers.is_empty());
+        assert!(settings.custom_headers.is_empty());
+        assert_eq!(settings.collection_scheme, Scheme::Https);
+        assert_eq!(settings.collection_path, None);
+        assert_eq!(settings.protocol, Protocol::Event);
+
+        let settings = PianoSettings::parse(&dict(&[
+            ("piano_site_id", "123456"),
+            ("piano_collection_domain", "localhost:8080"),
+            ("piano_collection_scheme", "http"),
+            ("piano_collection_path", "collect"),
+            ("piano_protocol", "as2"),
+        ]))
+        .unwrap();
+        assert_eq!(settings.collection_scheme, Scheme::Http);
+        assert_eq!(settings.collection_path, Some("/collect".to_string()));
+        assert_eq!(settings.protocol, Protocol::As2);
+    }
+
+    #[test]
+    fn missing_site() {
+        assert_eq!(
+            error(&[("piano_collection_domain", "xxxxxxx.pa-cd.com")]),
+            "Missing piano site id"
+        );
+        assert_eq!(
+            error(&[
+                ("piano_site_id", "123456"),
+                ("piano_collection_domain", " ")
+            ]),
+            "Missing piano collection domain"
+        );
+        // hostname sites may provide both
+        assert!(PianoSettings::parse(&dict(&[(
+            "piano_hostname_sites",
+            "example.com=123456@xxxxxxx.pa-cd.com"
+        )]))
+        .is_ok());
+    }
+
+    #[test]
+    fn invalid_site_id() {
+        assert_eq!(
+            error(&[
+                ("piano_site_id", "abc"),
+                ("piano_collection_domain", "xxxxxxx.pa-cd.com")
+            ]),
+            "Invalid piano_site_id setting: Site id must be numeric, got abc"
+        );
+        assert_eq!(
+            error(&[
+                ("piano_site_id", "123456"),
+                ("piano_collection_domain", "xxxxxxx.pa-cd.com"),
+                ("piano_site_routing", "country:FR=12a")
+            ]),
+            "Invalid piano_site_routing setting: Site id must be numeric, got 12a"
+        );
+    }
+
+    #[test]
+    fn invalid_collection_domain() {
+        for domain in [
+            "ftp://xxxxxxx.pa-cd.com",
+            "xxxxxxx.pa-cd.com/path",
+            "https://xxxxxxx.pa-cd.com/event",
+            "xxxxxxx..pa-cd.com",
+            "-xxxxxxx.pa-cd.com",
+            "xxxxxxx.pa-cd.com:0",
+            "xxxxxxx.pa-cd.com:https",
+        ] {
+            assert_eq!(
+                error(&[
+                    ("piano_site_id", "123456"),
+                    ("piano_collection_domain", domain)
+                ]),
+                format!(
+                    "Invalid piano_collection_domain setting: Collection domain must be a hostname, got {}",
+                    domain
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn normalized_collection_domain() {
+        for domain in [
+            "xxxxxxx.pa-cd.com",
+            " https://xxxxxxx.pa-cd.com/ ",
+            "HTTP://xxxxxxx.pa-cd.com//",
+            "xxxxxxx.pa-cd.com/",
+        ] {
+            assert_eq!(
+                normalize_collection_domain(domain).unwrap(),
+                "xxxxxxx.pa-cd.com"
+            );
+        }
+        assert_eq!(
+            normalize_collection_domain("http://localhost:8080/").unwrap(),
+            "localhost:8080"
+        );
+        assert_eq!(
+            PianoSettings::parse(&dict(&[
+                ("piano_site_id", "123456"),
+                ("piano_collection_domain", "https://xxxxxxx.pa-cd.com/")
+            ]))
+            .unwrap()
+            .collection_domain,
+            Some("xxxxxxx.pa-cd.com".to_string())
+        );
+    }
+
+    #[test]
+    fn client_headers() {
+        let settings = PianoSettings::parse(&dict(&[
+            ("piano_site_id", "123456"),
+            ("piano_collection_domain", "xxxxxxx.pa-cd.com"),
+            ("piano_send_client_ip", "true"),
+            ("piano_forwarded_headers", "accept-language,user-agent"),
+            ("piano_forward_client_headers", "false"),
+            ("piano_custom_headers", "Authorization=Bearer abc"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            settings.forwarded_headers,
+            vec![
+                ForwardedHeader::XForwardedFor,
+                ForwardedHeader::UserAgent,
+                ForwardedHeader::AcceptLanguage
+            ]
+        );
+        assert!(!settings.forward_client_headers);
+        assert_eq!(
+            settings.custom_headers,
+            vec![("authorization".to_string(), "Bearer abc".to_string())]
+        );
+    }
+
+    #[test]
+    fn strict_booleans() {
+        assert_eq!(
+            error(&[
+                ("piano_site_id", "123456"),
+                ("piano_collection_domain", "xxxxxxx.pa-cd.com"),
+                ("piano_collect_utm_as_properties", "yes")
+            ]),
+            "Invalid piano_collect_utm_as_properties setting: expected true or false, got yes"
+        );
+    }
+
+    #[test]
+    fn invalid_settings_are_named() {
+        assert!(error(&[
+            ("piano_site_id", "123456"),
+            ("piano_collection_domain", "xxxxxxx.pa-cd.com"),
+            ("piano_geo_granularity", "street")
+        ])
+        .starts_with("Invalid piano_geo_granularity setting: "));
+    }
+}
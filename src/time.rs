@@ -0,0 +1,134 @@
+use chrono::{DateTime, Datelike, FixedOffset, TimeZone, Timelike, Utc};
+
+// Local time of the client
+//
+// computed from the event timestamp and the client timezone (IANA name, e.g. Europe/Paris),
+// falling back to UTC when the timezone is empty or unknown
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LocalTime {
+    pub hour: u32,
+    // YYYY-MM-DD
+    pub date: String,
+    // ISO weekday, 1 (Monday) to 7 (Sunday)
+    pub weekday: u32,
+    // offset from UTC, in minutes
+    pub utc_offset: i32,
+}
+
+impl LocalTime {
+    // returns None if the timestamp is out of range
+    pub(crate) fn new(timestamp: i64, timezone: &str) -> Option<Self> {
+        let utc = Utc.timestamp_opt(timestamp, 0).single()?;
+        let offset = utc_offset(timezone.trim(), &utc).unwrap_or(0);
+        let datetime = utc.with_timezone(&FixedOffset::east_opt(offset)?);
+
+        Some(Self {
+            hour: datetime.hour(),
+            date: datetime.date_naive().format("%Y-%m-%d").to_string(),
+            weekday: datetime.weekday().number_from_monday(),
+            utc_offset: offset / 60,
+        })
+    }
+}
+
+// offset from UTC of the timezone at the given time, in seconds
+// returns None if the timezone is unknown
+//
+// the chrono_tz crate (full IANA database) increases the weight of the binary by 0.9MB... this is huge,
+// so it's only used with the tz-full feature, a compact table of the most common timezones is used otherwise
+#[cfg(feature = "tz-full")]
+fn utc_offset(timezone: &str, utc: &DateTime<Utc>) -> Option<i32> {
+    use chrono::Offset;
+    use std::str::FromStr;
+
+    let tz = chrono_tz::Tz::from_str(timezone).ok()?;
+    Some(utc.with_timezone(&tz).offset().fix().local_minus_utc())
+}
+
+#[cfg(not(feature = "tz-full"))]
+fn utc_offset(timezone: &str, utc: &DateTime<Utc>) -> Option<i32> {
+    crate::tz::utc_offset(timezone, utc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timestamp(datetime: &str) -> i64 {
+        DateTime::parse_from_rfc3339(datetime).unwrap().timestamp()
+    }
+
+    #[test]
+    fn utc_fallback() {
+        let expected = LocalTime {
+            hour: 13,
+            date: "2024-05-01".to_string(),
+            weekday: 3,
+            utc_offset: 0,
+        };
+        let ts = timestamp("2024-05-01T13:45:00Z");
+        assert_eq!(LocalTime::new(ts, ""), Some(expected.clone()));
+        assert_eq!(
+            LocalTime::new(ts, "Mars/Olympus_Mons"),
+            Some(expected.clone())
+        );
+        assert_eq!(LocalTime::new(ts, "UTC"), Some(expected));
+    }
+
+    #[test]
+    fn out_of_range_timestamp() {
+        assert_eq!(LocalTime::new(i64::MAX, "Europe/Paris"), None);
+        assert_eq!(LocalTime::new(i64::MIN, ""), None);
+    }
+
+    #[test]
+    fn europe_paris_spring_forward() {
+        let before = LocalTime::new(timestamp("2024-03-31T00:59:59Z"), "Europe/Paris").unwrap();
+        assert_eq!(before.hour, 1);
+        assert_eq!(before.utc_offset, 60);
+        let after = LocalTime::new(timestamp("2024-03-31T01:00:00Z"), "Europe/Paris").unwrap();
+        assert_eq!(after.hour, 3);
+        assert_eq!(after.utc_offset, 120);
+        assert_eq!(after.date, "2024-03-31");
+        assert_eq!(after.weekday, 7);
+    }
+
+    #[test]
+    fn europe_paris_fall_back() {
+        let before = LocalTime::new(timestamp("2024-10-27T00:59:59Z"), "Europe/Paris").unwrap();
+        assert_eq!(before.hour, 2);
+        assert_eq!(before.utc_offset, 120);
+        let after = LocalTime::new(timestamp("2024-10-27T01:00:00Z"), "Europe/Paris").unwrap();
+        assert_eq!(after.hour, 2);
+        assert_eq!(after.utc_offset, 60);
+    }
+
+    #[test]
+    fn america_new_york_spring_forward() {
+        let before = LocalTime::new(timestamp("2024-03-10T06:59:59Z"), "America/New_York").unwrap();
+        assert_eq!(before.hour, 1);
+        assert_eq!(before.utc_offset, -300);
+        let after = LocalTime::new(timestamp("2024-03-10T07:00:00Z"), "America/New_York").unwrap();
+        assert_eq!(after.hour, 3);
+        assert_eq!(after.utc_offset, -240);
+    }
+
+    #[test]
+    fn australia_sydney_fall_back_and_date_change() {
+        let before = LocalTime::new(timestamp("2024-04-06T15:59:59Z"), "Australia/Sydney").unwrap();
+        assert_eq!(before.hour, 2);
+        assert_eq!(before.date, "2024-04-07");
+        assert_eq!(before.weekday, 7);
+        assert_eq!(before.utc_offset, 660);
+        let after = LocalTime::new(timestamp("2024-04-06T16:00:00Z"), "Australia/Sydney").unwrap();
+        assert_eq!(after.hour, 2);
+        assert_eq!(after.utc_offset, 600);
+    }
+
+    #[test]
+    fn half_hour_offset() {
+        let local = LocalTime::new(timestamp("2024-01-01T00:00:00Z"), "Asia/Kolkata").unwrap();
+        assert_eq!(local.hour, 5);
+        assert_eq!(local.utc_offset, 330);
+    }
+}
@@ -0,0 +1,411 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc, Weekday};
+
+// Compact timezone database
+//
+// chrono_tz embeds the whole IANA database, which increases the weight of the binary by ~0.9MB.
+// This table only covers the most commonly seen timezones, with their current standard offset and
+// daylight saving time rule (past rule changes are ignored). Unknown timezones fall back to UTC,
+// the full database can be used instead with the tz-full feature.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DstRule {
+    None,
+    // last Sunday of March to last Sunday of October, at 01:00 UTC
+    Eu,
+    // second Sunday of March to first Sunday of November, at 02:00 local time
+    Us,
+    // first Sunday of October to first Sunday of April, at 02:00 local standard time
+    Au,
+    // last Sunday of September to first Sunday of April, at 02:00 local standard time
+    Nz,
+}
+
+// (timezone, standard offset in minutes, daylight saving time rule)
+const ZONES: &[(&str, i32, DstRule)] = &[
+    // UTC
+    ("UTC", 0, DstRule::None),
+    ("Etc/UTC", 0, DstRule::None),
+    ("GMT", 0, DstRule::None),
+    ("Etc/GMT", 0, DstRule::None),
+    // Europe
+    ("WET", 0, DstRule::Eu),
+    ("CET", 60, DstRule::Eu),
+    ("MET", 60, DstRule::Eu),
+    ("EET", 120, DstRule::Eu),
+    ("Atlantic/Azores", -60, DstRule::Eu),
+    ("Atlantic/Canary", 0, DstRule::Eu),
+    ("Atlantic/Faroe", 0, DstRule::Eu),
+    ("Atlantic/Madeira", 0, DstRule::Eu),
+    ("Europe/Dublin", 0, DstRule::Eu),
+    ("Europe/Guernsey", 0, DstRule::Eu),
+    ("Europe/Isle_of_Man", 0, DstRule::Eu),
+    ("Europe/Jersey", 0, DstRule::Eu),
+    ("Europe/Lisbon", 0, DstRule::Eu),
+    ("Europe/London", 0, DstRule::Eu),
+    ("Africa/Ceuta", 60, DstRule::Eu),
+    ("Arctic/Longyearbyen", 60, DstRule::Eu),
+    ("Europe/Amsterdam", 60, DstRule::Eu),
+    ("Europe/Andorra", 60, DstRule::Eu),
+    ("Europe/Belgrade", 60, DstRule::Eu),
+    ("Europe/Berlin", 60, DstRule::Eu),
+    ("Europe/Bratislava", 60, DstRule::Eu),
+    ("Europe/Brussels", 60, DstRule::Eu),
+    ("Europe/Budapest", 60, DstRule::Eu),
+    ("Europe/Busingen", 60, DstRule::Eu),
+    ("Europe/Copenhagen", 60, DstRule::Eu),
+    ("Europe/Gibraltar", 60, DstRule::Eu),
+    ("Europe/Ljubljana", 60, DstRule::Eu),
+    ("Europe/Luxembourg", 60, DstRule::Eu),
+    ("Europe/Madrid", 60, DstRule::Eu),
+    ("Europe/Malta", 60, DstRule::Eu),
+    ("Europe/Monaco", 60, DstRule::Eu),
+    ("Europe/Oslo", 60, DstRule::Eu),
+    ("Europe/Paris", 60, DstRule::Eu),
+    ("Europe/Podgorica", 60, DstRule::Eu),
+    ("Europe/Prague", 60, DstRule::Eu),
+    ("Europe/Rome", 60, DstRule::Eu),
+    ("Europe/San_Marino", 60, DstRule::Eu),
+    ("Europe/Sarajevo", 60, DstRule::Eu),
+    ("Europe/Skopje", 60, DstRule::Eu),
+    ("Europe/Stockholm", 60, DstRule::Eu),
+    ("Europe/Tirane", 60, DstRule::Eu),
+    ("Europe/Vaduz", 60, DstRule::Eu),
+    ("Europe/Vatican", 60, DstRule::Eu),
+    ("Europe/Vienna", 60, DstRule::Eu),
+    ("Europe/Warsaw", 60, DstRule::Eu),
+    ("Europe/Zagreb", 60, DstRule::Eu),
+    ("Europe/Zurich", 60, DstRule::Eu),
+    ("Asia/Famagusta", 120, DstRule::Eu),
+    ("Asia/Nicosia", 120, DstRule::Eu),
+    ("Europe/Athens", 120, DstRule::Eu),
+    ("Europe/Bucharest", 120, DstRule::Eu),
+    ("Europe/Helsinki", 120, DstRule::Eu),
+    ("Europe/Kiev", 120, DstRule::Eu),
+    ("Europe/Kyiv", 120, DstRule::Eu),
+    ("Europe/Mariehamn", 120, DstRule::Eu),
+    ("Europe/Nicosia", 120, DstRule::Eu),
+    ("Europe/Riga", 120, DstRule::Eu),
+    ("Europe/Sofia", 120, DstRule::Eu),
+    ("Europe/Tallinn", 120, DstRule::Eu),
+    ("Europe/Vilnius", 120, DstRule::Eu),
+    ("Europe/Kaliningrad", 120, DstRule::None),
+    ("Europe/Istanbul", 180, DstRule::None),
+    ("Europe/Minsk", 180, DstRule::None),
+    ("Europe/Moscow", 180, DstRule::None),
+    ("Europe/Samara", 240, DstRule::None),
+    // Africa
+    ("Africa/Abidjan", 0, DstRule::None),
+    ("Africa/Accra", 0, DstRule::None),
+    ("Africa/Dakar", 0, DstRule::None),
+    ("Africa/Algiers", 60, DstRule::None),
+    ("Africa/Kinshasa", 60, DstRule::None),
+    ("Africa/Lagos", 60, DstRule::None),
+    ("Africa/Tunis", 60, DstRule::None),
+    ("Africa/Johannesburg", 120, DstRule::None),
+    ("Africa/Addis_Ababa", 180, DstRule::None),
+    ("Africa/Nairobi", 180, DstRule::None),
+    // Asia
+    ("Asia/Baghdad", 180, DstRule::None),
+    ("Asia/Kuwait", 180, DstRule::None),
+    ("Asia/Qatar", 180, DstRule::None),
+    ("Asia/Riyadh", 180, DstRule::None),
+    ("Asia/Tehran", 210, DstRule::None),
+    ("Asia/Baku", 240, DstRule::None),
+    ("Asia/Dubai", 240, DstRule::None),
+    ("Asia/Tbilisi", 240, DstRule::None),
+    ("Asia/Yerevan", 240, DstRule::None),
+    ("Asia/Almaty", 300, DstRule::None),
+    ("Asia/Karachi", 300, DstRule::None),
+    ("Asia/Tashkent", 300, DstRule::None),
+    ("Asia/Calcutta", 330, DstRule::None),
+    ("Asia/Colombo", 330, DstRule::None),
+    ("Asia/Kolkata", 330, DstRule::None),
+    ("Asia/Kathmandu", 345, DstRule::None),
+    ("Asia/Dhaka", 360, DstRule::None),
+    ("Asia/Yangon", 390, DstRule::None),
+    ("Asia/Bangkok", 420, DstRule::None),
+    ("Asia/Ho_Chi_Minh", 420, DstRule::None),
+    ("Asia/Jakarta", 420, DstRule::None),
+    ("Asia/Saigon", 420, DstRule::None),
+    ("Asia/Hong_Kong", 480, DstRule::None),
+    ("Asia/Kuala_Lumpur", 480, DstRule::None),
+    ("Asia/Manila", 480, DstRule::None),
+    ("Asia/Shanghai", 480, DstRule::None),
+    ("Asia/Singapore", 480, DstRule::None),
+    ("Asia/Taipei", 480, DstRule::None),
+    ("Asia/Seoul", 540, DstRule::None),
+    ("Asia/Tokyo", 540, DstRule::None),
+    // Oceania
+    ("Australia/Perth", 480, DstRule::None),
+    ("Australia/Darwin", 570, DstRule::None),
+    ("Australia/Adelaide", 570, DstRule::Au),
+    ("Australia/Broken_Hill", 570, DstRule::Au),
+    ("Australia/Brisbane", 600, DstRule::None),
+    ("Australia/Canberra", 600, DstRule::Au),
+    ("Australia/Hobart", 600, DstRule::Au),
+    ("Australia/Melbourne", 600, DstRule::Au),
+    ("Australia/Sydney", 600, DstRule::Au),
+    ("Pacific/Auckland", 720, DstRule::Nz),
+    ("Pacific/Honolulu", -600, DstRule::None),
+    // Americas
+    ("America/St_Johns", -210, DstRule::Us),
+    ("America/Halifax", -240, DstRule::Us),
+    ("America/Caracas", -240, DstRule::None),
+    ("America/La_Paz", -240, DstRule::None),
+    ("America/Puerto_Rico", -240, DstRule::None),
+    ("America/Santo_Domingo", -240, DstRule::None),
+    ("America/Argentina/Buenos_Aires", -180, DstRule::None),
+    ("America/Buenos_Aires", -180, DstRule::None),
+    ("America/Montevideo", -180, DstRule::None),
+    ("America/Sao_Paulo", -180, DstRule::None),
+    ("America/Detroit", -300, DstRule::Us),
+    ("America/Indiana/Indianapolis", -300, DstRule::Us),
+    ("America/Kentucky/Louisville", -300, DstRule::Us),
+    ("America/Montreal", -300, DstRule::Us),
+    ("America/Nassau", -300, DstRule::Us),
+    ("America/New_York", -300, DstRule::Us),
+    ("America/Toronto", -300, DstRule::Us),
+    ("US/Eastern", -300, DstRule::Us),
+    ("EST5EDT", -300, DstRule::Us),
+    ("EST", -300, DstRule::None),
+    ("America/Bogota", -300, DstRule::None),
+    ("America/Cancun", -300, DstRule::None),
+    ("America/Lima", -300, DstRule::None),
+    ("America/Panama", -300, DstRule::None),
+    ("America/Chicago", -360, DstRule::Us),
+    ("America/Winnipeg", -360, DstRule::Us),
+    ("US/Central", -360, DstRule::Us),
+    ("CST6CDT", -360, DstRule::Us),
+    ("America/Costa_Rica", -360, DstRule::None),
+    ("America/El_Salvador", -360, DstRule::None),
+    ("America/Guatemala", -360, DstRule::None),
+    ("America/Mexico_City", -360, DstRule::None),
+    ("America/Monterrey", -360, DstRule::None),
+    ("America/Regina", -360, DstRule::None),
+    ("America/Boise", -420, DstRule::Us),
+    ("America/Denver", -420, DstRule::Us),
+    ("America/Edmonton", -420, DstRule::Us),
+    ("US/Mountain", -420, DstRule::Us),
+    ("MST7MDT", -420, DstRule::Us),
+    ("America/Phoenix", -420, DstRule::None),
+    ("MST", -420, DstRule::None),
+    ("America/Los_Angeles", -480, DstRule::Us),
+    ("America/Tijuana", -480, DstRule::Us),
+    ("America/Vancouver", -480, DstRule::Us),
+    ("US/Pacific", -480, DstRule::Us),
+    ("PST8PDT", -480, DstRule::Us),
+    ("America/Anchorage", -540, DstRule::Us),
+    ("US/Alaska", -540, DstRule::Us),
+    ("HST", -600, DstRule::None),
+    ("US/Hawaii", -600, DstRule::None),
+];
+
+// offset from UTC of the timezone at the given time, in seconds
+// returns None if the timezone is not in the table
+pub(crate) fn utc_offset(timezone: &str, utc: &DateTime<Utc>) -> Option<i32> {
+    let (_, standard_offset, rule) = ZONES.iter().find(|(name, _, _)| *name == timezone)?;
+    let standard_offset = standard_offset * 60;
+
+    let offset = if is_dst(*rule, standard_offset, utc) {
+        standard_offset + 3600
+    } else {
+        standard_offset
+    };
+    Some(offset)
+}
+
+fn is_dst(rule: DstRule, standard_offset: i32, utc: &DateTime<Utc>) -> bool {
+    let year = utc.year();
+    let daylight_offset = standard_offset + 3600;
+    match rule {
+        DstRule::None => false,
+        DstRule::Eu => {
+            let start = transition(last_sunday(year, 3), 1, 0);
+            let end = transition(last_sunday(year, 10), 1, 0);
+            *utc >= start && *utc < end
+        }
+        DstRule::Us => {
+            let start = transition(nth_sunday(year, 3, 2), 2, standard_offset);
+            let end = transition(nth_sunday(year, 11, 1), 2, daylight_offset);
+            *utc >= start && *utc < end
+        }
+        DstRule::Au => {
+            let end = transition(nth_sunday(year, 4, 1), 3, daylight_offset);
+            let start = transition(nth_sunday(year, 10, 1), 2, standard_offset);
+            *utc < end || *utc >= start
+        }
+        DstRule::Nz => {
+            let end = transition(nth_sunday(year, 4, 1), 3, daylight_offset);
+            let start = transition(last_sunday(year, 9), 2, standard_offset);
+            *utc < end || *utc >= start
+        }
+    }
+}
+
+// UTC time of a transition happening at the given local hour, with the given offset (in seconds)
+fn transition(date: NaiveDate, hour: i64, offset: i32) -> DateTime<Utc> {
+    date.and_time(chrono::NaiveTime::MIN).and_utc() + Duration::hours(hour)
+        - Duration::seconds(offset as i64)
+}
+
+fn nth_sunday(year: i32, month: u32, n: u8) -> NaiveDate {
+    NaiveDate::from_weekday_of_month_opt(year, month, Weekday::Sun, n).unwrap_or_default()
+}
+
+fn last_sunday(year: i32, month: u32) -> NaiveDate {
+    // a month has 4 or 5 Sundays
+    NaiveDate::from_weekday_of_month_opt(year, month, Weekday::Sun, 5)
+        .unwrap_or_else(|| nth_sunday(year, month, 4))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offset(timezone: &str, datetime: &str) -> Option<i32> {
+        let utc = DateTime::parse_from_rfc3339(datetime)
+            .unwrap()
+            .with_timezone(&Utc);
+        utc_offset(timezone, &utc).map(|offset| offset / 60)
+    }
+
+    #[test]
+    fn unknown_timezone() {
+        assert_eq!(offset("Mars/Olympus_Mons", "2024-01-01T00:00:00Z"), None);
+        assert_eq!(offset("", "2024-01-01T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn fixed_offsets() {
+        assert_eq!(offset("UTC", "2024-07-01T00:00:00Z"), Some(0));
+        assert_eq!(offset("Asia/Kolkata", "2024-07-01T00:00:00Z"), Some(330));
+        assert_eq!(
+            offset("America/Phoenix", "2024-07-01T00:00:00Z"),
+            Some(-420)
+        );
+    }
+
+    #[test]
+    fn last_sundays() {
+        assert_eq!(
+            last_sunday(2024, 3),
+            NaiveDate::from_ymd_opt(2024, 3, 31).unwrap()
+        );
+        assert_eq!(
+            last_sunday(2024, 10),
+            NaiveDate::from_ymd_opt(2024, 10, 27).unwrap()
+        );
+        assert_eq!(
+            last_sunday(2025, 3),
+            NaiveDate::from_ymd_opt(2025, 3, 30).unwrap()
+        );
+        assert_eq!(
+            last_sunday(2026, 2),
+            NaiveDate::from_ymd_opt(2026, 2, 22).unwrap()
+        );
+    }
+
+    #[test]
+    fn eu_rule() {
+        assert_eq!(offset("Europe/Paris", "2024-03-31T00:59:59Z"), Some(60));
+        assert_eq!(offset("Europe/Paris", "2024-03-31T01:00:00Z"), Some(120));
+        assert_eq!(offset("Europe/Paris", "2024-10-27T00:59:59Z"), Some(120));
+        assert_eq!(offset("Europe/Paris", "2024-10-27T01:00:00Z"), Some(60));
+        assert_eq!(offset("Europe/London", "2025-03-30T01:00:00Z"), Some(60));
+        assert_eq!(offset("Europe/Athens", "2025-03-30T00:59:59Z"), Some(120));
+    }
+
+    #[test]
+    fn us_rule() {
+        assert_eq!(
+            offset("America/New_York", "2024-03-10T06:59:59Z"),
+            Some(-300)
+        );
+        assert_eq!(
+            offset("America/New_York", "2024-03-10T07:00:00Z"),
+            Some(-240)
+        );
+        assert_eq!(
+            offset("America/New_York", "2024-11-03T05:59:59Z"),
+            Some(-240)
+        );
+        assert_eq!(
+            offset("America/New_York", "2024-11-03T06:00:00Z"),
+            Some(-300)
+        );
+        assert_eq!(
+            offset("America/Los_Angeles", "2024-03-10T10:00:00Z"),
+            Some(-420)
+        );
+    }
+
+    #[test]
+    fn au_rule() {
+        assert_eq!(
+            offset("Australia/Sydney", "2024-04-06T15:59:59Z"),
+            Some(660)
+        );
+        assert_eq!(
+            offset("Australia/Sydney", "2024-04-06T16:00:00Z"),
+            Some(600)
+        );
+        assert_eq!(
+            offset("Australia/Sydney", "2024-10-05T15:59:59Z"),
+            Some(600)
+        );
+        assert_eq!(
+            offset("Australia/Sydney", "2024-10-05T16:00:00Z"),
+            Some(660)
+        );
+        assert_eq!(
+            offset("Australia/Adelaide", "2024-01-01T00:00:00Z"),
+            Some(630)
+        );
+    }
+
+    #[test]
+    fn nz_rule() {
+        assert_eq!(
+            offset("Pacific/Auckland", "2024-04-06T13:59:59Z"),
+            Some(780)
+        );
+        assert_eq!(
+            offset("Pacific/Auckland", "2024-04-06T14:00:00Z"),
+            Some(720)
+        );
+        assert_eq!(
+            offset("Pacific/Auckland", "2024-09-28T13:59:59Z"),
+            Some(720)
+        );
+        assert_eq!(
+            offset("Pacific/Auckland", "2024-09-28T14:00:00Z"),
+            Some(780)
+        );
+    }
+
+    #[cfg(feature = "tz-full")]
+    #[test]
+    fn matches_full_database() {
+        use chrono::Offset;
+        use std::str::FromStr;
+
+        // every hour for two years, since the last rule change of the table (Asia/Almaty, March 2024)
+        let start = DateTime::parse_from_rfc3339("2024-03-01T00:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        for (timezone, _, _) in ZONES {
+            let tz = chrono_tz::Tz::from_str(timezone).unwrap();
+            for hours in 0..2 * 365 * 24 {
+                let utc = start + Duration::hours(hours);
+                let expected = utc.with_timezone(&tz).offset().fix().local_minus_utc();
+                assert_eq!(
+                    utc_offset(timezone, &utc),
+                    Some(expected),
+                    "{} at {}",
+                    timezone,
+                    utc
+                );
+            }
+        }
+    }
+}
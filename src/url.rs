@@ -0,0 +1,127 @@
+use crate::endpoint::Scheme;
+
+// Request URL builder
+//
+// Query parameters are percent-encoded and kept in the order they are added,
+// parameters without value are omitted
+#[derive(Debug, Clone)]
+pub(crate) struct UrlBuilder {
+    base: String,
+    parameters: Vec<(String, String)>,
+}
+
+impl UrlBuilder {
+    pub(crate) fn new(scheme: Scheme, domain: &str, path: &str) -> Self {
+        Self {
+            base: format!("{}://{}{}", scheme.as_str(), domain, path),
+            parameters: vec![],
+        }
+    }
+
+    pub(crate) fn parameter(mut self, key: &str, value: &str) -> Self {
+        self.parameters.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub(crate) fn optional_parameter(self, key: &str, value: Option<&str>) -> Self {
+        match value {
+            Some(value) => self.parameter(key, value),
+            None => self,
+        }
+    }
+
+    pub(crate) fn parameters(mut self, parameters: Vec<(String, String)>) -> Self {
+        self.parameters.extend(parameters);
+        self
+    }
+
+    pub(crate) fn build(&self) -> String {
+        if self.parameters.is_empty() {
+            return self.base.clone();
+        }
+        let query = self
+            .parameters
+            .iter()
+            .map(|(key, value)| format!("{}={}", percent_encode(key), percent_encode(value)))
+            .collect::<Vec<_>>()
+            .join("&");
+        format!("{}?{}", self.base, query)
+    }
+}
+
+// every byte but the unreserved characters of RFC 3986 is percent-encoded
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_without_parameters() {
+        let url = UrlBuilder::new(Scheme::Https, "xxxxxxx.pa-cd.com", "/event").build();
+        assert_eq!(url, "https://xxxxxxx.pa-cd.com/event");
+    }
+
+    #[test]
+    fn url_with_parameters() {
+        let url = UrlBuilder::new(Scheme::Http, "localhost:8080", "/collect")
+            .parameter("s", "123456")
+            .parameter("idclient", "a6f4c1e2-0b9d-4a55-9b8e-2f6d1c3b7a90")
+            .build();
+        assert_eq!(
+            url,
+            "http://localhost:8080/collect?s=123456&idclient=a6f4c1e2-0b9d-4a55-9b8e-2f6d1c3b7a90"
+        );
+    }
+
+    #[test]
+    fn reserved_characters_are_encoded() {
+        let url = UrlBuilder::new(Scheme::Https, "xxxxxxx.pa-cd.com", "/event")
+            .parameter("s", "12&x=1")
+            .parameter("idclient", "a b/c?d#e=f+g%h")
+            .parameter("p", "été")
+            .parameter("k&y", "")
+            .build();
+        assert_eq!(
+            url,
+            "https://xxxxxxx.pa-cd.com/event?s=12%26x%3D1&idclient=a%20b%2Fc%3Fd%23e%3Df%2Bg%25h\
+             &p=%C3%A9t%C3%A9&k%26y="
+        );
+    }
+
+    #[test]
+    fn optional_parameters() {
+        let url = UrlBuilder::new(Scheme::Https, "xxxxxxx.pa-cd.com", "/event")
+            .parameter("s", "123456")
+            .optional_parameter("idclient", None)
+            .optional_parameter("x1", Some("a"))
+            .build();
+        assert_eq!(url, "https://xxxxxxx.pa-cd.com/event?s=123456&x1=a");
+    }
+
+    #[test]
+    fn parameters_keep_their_order() {
+        let url = UrlBuilder::new(Scheme::Https, "xxxxxxx.pa-cd.com", "/hit.xiti")
+            .parameters(vec![
+                ("s".to_string(), "1".to_string()),
+                ("p".to_string(), "home".to_string()),
+                ("ref".to_string(), "https://example.com/?a=1".to_string()),
+            ])
+            .build();
+        assert_eq!(
+            url,
+            "https://xxxxxxx.pa-cd.com/hit.xiti?s=1&p=home&ref=https%3A%2F%2Fexample.com%2F%3Fa%3D1"
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use crate::piano_payload::{ChUa, PianoData};
+
+// User-Agent parsing
+//
+// Safari and Firefox don't send client hints, so browser, OS and mobile flag are derived from the
+// User-Agent string instead. Brands are named as in Sec-CH-UA when the browser sends client hints.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct UserAgent {
+    pub browser: Option<(String, String)>,
+    pub os: Option<(String, String)>,
+    // as Sec-CH-UA-Mobile, true for phones only
+    pub mobile: bool,
+}
+
+// (token, brand), the first token found wins, so more specific tokens come first
+const BROWSERS: &[(&str, &str)] = &[
+    ("Edg/", "Microsoft Edge"),
+    ("EdgA/", "Microsoft Edge"),
+    ("EdgiOS/", "Microsoft Edge"),
+    ("OPR/", "Opera"),
+    ("OPiOS/", "Opera"),
+    ("SamsungBrowser/", "Samsung Internet"),
+    ("Firefox/", "Firefox"),
+    ("FxiOS/", "Firefox"),
+    ("CriOS/", "Google Chrome"),
+    ("Chromium/", "Chromium"),
+    ("Chrome/", "Google Chrome"),
+];
+
+impl UserAgent {
+    pub(crate) fn parse(user_agent: &str) -> Self {
+        Self {
+            browser: browser(user_agent),
+            os: os(user_agent),
+            mobile: is_mobile(user_agent),
+        }
+    }
+
+    // fill the ch_ua_* fields not already set from client hints
+    pub(crate) fn apply(&self, data: &mut PianoData) {
+        if let Some((brand, version)) = &self.browser {
+            if data.ch_ua.is_empty() {
+                let major = version.split('.').next().unwrap_or_default();
+                data.ch_ua = vec![ChUa {
+                    brand: brand.clone(),
+                    version: major.to_string(),
+                }];
+            }
+            if data.ch_ua_full_version_list.is_empty() {
+                data.ch_ua_full_version_list = vec![ChUa {
+                    brand: brand.clone(),
+                    version: version.clone(),
+                }];
+            }
+            if data.ch_ua_full_version.is_empty() {
+                data.ch_ua_full_version = version.clone();
+            }
+        }
+        if let Some((name, version)) = &self.os {
+            if data.ch_ua_platform.is_empty() {
+                data.ch_ua_platform = name.clone();
+            }
+            if data.ch_ua_platform_version.is_empty() {
+                data.ch_ua_platform_version = version.clone();
+            }
+        }
+        if self.mobile {
+            data.ch_ua_mobile = true;
+        }
+    }
+}
+
+fn browser(user_agent: &str) -> Option<(String, String)> {
+    for (token, brand) in BROWSERS {
+        if let Some(version) = version_after(user_agent, token) {
+            return Some((brand.to_string(), version));
+        }
+    }
+    // Safari (and other WebKit browsers) give their version in Version/
+    if user_agent.contains("Safari/") || user_agent.contains("AppleWebKit/") {
+        if let Some(version) = version_after(user_agent, "Version/") {
+            return Some(("Safari".to_string(), version));
+        }
+    }
+    None
+}
+
+fn os(user_agent: &str) -> Option<(String, String)> {
+    if let Some(version) = version_after(user_agent, "Windows NT ") {
+        let version = match version.as_str() {
+            "10.0" => "10",
+            "6.3" => "8.1",
+            "6.2" => "8",
+            "6.1" => "7",
+            version => version,
+        };
+        return Some(("Windows".to_string(), version.to_string()));
+    }
+    if user_agent.contains("Windows") {
+        return Some(("Windows".to_string(), String::new()));
+    }
+    // iOS user agents contain "like Mac OS X", check them first
+    if user_agent.contains("iPhone") || user_agent.contains("iPad") || user_agent.contains("iPod") {
+        let version = version_after(user_agent, "iPhone OS ")
+            .or_else(|| version_after(user_agent, "CPU OS "))
+            .unwrap_or_default();
+        return Some(("iOS".to_string(), version));
+    }
+    // Android user agents contain "Linux", check them first
+    if user_agent.contains("Android") {
+        let version = version_after(user_agent, "Android ").unwrap_or_default();
+        return Some(("Android".to_string(), version));
+    }
+    if user_agent.contains("CrOS") {
+        return Some(("Chrome OS".to_string(), String::new()));
+    }
+    if let Some(version) = version_after(user_agent, "Mac OS X ") {
+        return Some(("macOS".to_string(), version));
+    }
+    if user_agent.contains("Macintosh") {
+        return Some(("macOS".to_string(), String::new()));
+    }
+    if user_agent.contains("Linux") {
+        return Some(("Linux".to_string(), String::new()));
+    }
+    None
+}
+
+// tablets are not mobile, although iPad user agents contain "Mobile"
+fn is_mobile(user_agent: &str) -> bool {
+    let tablet = user_agent.contains("iPad")
+        || user_agent.contains("Tablet")
+        || (user_agent.contains("Android") && !user_agent.contains("Mobile"));
+    !tablet
+        && (user_agent.contains("Mobi")
+            || user_agent.contains("iPhone")
+            || user_agent.contains("iPod"))
+}
+
+// dotted version following the token, e.g. 17.4.1 for "iPhone OS 17_4_1"
+fn version_after(user_agent: &str, token: &str) -> Option<String> {
+    let (_, rest) = user_agent.split_once(token)?;
+    let version: String = rest
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.' || *c == '_')
+        .map(|c| if c == '_' { '.' } else { c })
+        .collect();
+    let version = version.trim_end_matches('.');
+    if version.is_empty() {
+        None
+    } else {
+        Some(version.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(name: &str, version: &str) -> Option<(String, String)> {
+        Some((name.to_string(), version.to_string()))
+    }
+
+    #[test]
+    fn safari_macos() {
+        let ua = UserAgent::parse("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4.1 Safari/605.1.15");
+        assert_eq!(ua.browser, pair("Safari", "17.4.1"));
+        assert_eq!(ua.os, pair("macOS", "10.15.7"));
+        assert!(!ua.mobile);
+    }
+
+    #[test]
+    fn safari_iphone() {
+        let ua = UserAgent::parse("Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Mobile/15E148 Safari/604.1");
+        assert_eq!(ua.browser, pair("Safari", "17.4"));
+        assert_eq!(ua.os, pair("iOS", "17.4"));
+        assert!(ua.mobile);
+    }
+
+    #[test]
+    fn safari_ipad() {
+        let ua = UserAgent::parse("Mozilla/5.0 (iPad; CPU OS 16_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.6 Mobile/15E148 Safari/604.1");
+        assert_eq!(ua.os, pair("iOS", "16.6"));
+        assert!(!ua.mobile);
+    }
+
+    #[test]
+    fn firefox_windows_and_android() {
+        let ua = UserAgent::parse(
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:125.0) Gecko/20100101 Firefox/125.0",
+        );
+        assert_eq!(ua.browser, pair("Firefox", "125.0"));
+        assert_eq!(ua.os, pair("Windows", "10"));
+        assert!(!ua.mobile);
+
+        let ua = UserAgent::parse(
+            "Mozilla/5.0 (Android 14; Mobile; rv:125.0) Gecko/125.0 Firefox/125.0",
+        );
+        assert_eq!(ua.browser, pair("Firefox", "125.0"));
+        assert_eq!(ua.os, pair("Android", "14"));
+        assert!(ua.mobile);
+    }
+
+    #[test]
+    fn firefox_ios() {
+        let ua = UserAgent::parse("Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) FxiOS/125.0 Mobile/15E148 Safari/605.1.15");
+        assert_eq!(ua.browser, pair("Firefox", "125.0"));
+        assert_eq!(ua.os, pair("iOS", "17.4"));
+    }
+
+    #[test]
+    fn chromium_based_browsers() {
+        let ua = UserAgent::parse("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36 Edg/124.0.2478.51");
+        assert_eq!(ua.browser, pair("Microsoft Edge", "124.0.2478.51"));
+
+        let ua = UserAgent::parse("Mozilla/5.0 (Linux; Android 10; K) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36");
+        assert_eq!(ua.browser, pair("Google Chrome", "124.0.0.0"));
+        assert_eq!(ua.os, pair("Android", "10"));
+        assert!(!ua.mobile);
+
+        let ua = UserAgent::parse("Mozilla/5.0 (X11; CrOS x86_64 14541.0.0) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36");
+        assert_eq!(ua.os, pair("Chrome OS", ""));
+    }
+
+    #[test]
+    fn unknown_user_agent() {
+        let ua = UserAgent::parse("curl/8.4.0");
+        assert_eq!(ua, UserAgent::default());
+        assert_eq!(UserAgent::parse(""), UserAgent::default());
+    }
+
+    #[test]
+    fn apply_keeps_client_hints() {
+        let mut data = PianoData {
+            ch_ua_platform: "macOS".to_string(),
+            ch_ua_platform_version: "14.4.1".to_string(),
+            ..PianoData::default()
+        };
+        UserAgent::parse("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4.1 Safari/605.1.15").apply(&mut data);
+        assert_eq!(data.ch_ua[0].brand, "Safari");
+        assert_eq!(data.ch_ua[0].version, "17");
+        assert_eq!(data.ch_ua_full_version_list[0].version, "17.4.1");
+        assert_eq!(data.ch_ua_full_version, "17.4.1");
+        assert_eq!(data.ch_ua_platform_version, "14.4.1");
+        assert!(!data.ch_ua_mobile);
+    }
+}
@@ -0,0 +1,39 @@
+// matches a value against a pattern where * stands for any sequence of characters
+pub(crate) fn matches(pattern: &str, value: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == value;
+    }
+
+    let first = parts[0];
+    let last = parts[parts.len() - 1];
+    if value.len() < first.len() + last.len() || !value.starts_with(first) {
+        return false;
+    }
+    let mut rest = &value[first.len()..];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_patterns() {
+        assert!(matches("/404", "/404"));
+        assert!(!matches("/404", "/4040"));
+        assert!(matches("*", "/anything"));
+        assert!(matches("/errors/*", "/errors/"));
+        assert!(matches("*.example.com", "www.example.com"));
+        assert!(!matches("*.example.com", "example.com"));
+        assert!(matches("/a*b*c", "/a-b-c"));
+        assert!(!matches("/a*b*c", "/a-c-b"));
+        assert!(!matches("/ab*ba", "/aba"));
+    }
+}